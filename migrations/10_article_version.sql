@@ -0,0 +1,3 @@
+ALTER TABLE `article`
+  ADD COLUMN `version` int NOT NULL DEFAULT 1 AFTER `publish_at`,
+  ADD COLUMN `require_if_match` tinyint(1) NOT NULL DEFAULT 0 AFTER `version`;
@@ -0,0 +1 @@
+ALTER TABLE `article` ADD COLUMN `body_html` longtext NULL AFTER `body`;
@@ -0,0 +1 @@
+ALTER TABLE `article` MODIFY `slug` varchar(255) NOT NULL;
@@ -0,0 +1,7 @@
+CREATE TABLE `slug_history` (
+  `slug` varchar(255) NOT NULL,
+  `article_id` varchar(36) NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`slug`),
+  KEY `key_article_id` (`article_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
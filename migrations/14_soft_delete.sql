@@ -0,0 +1,3 @@
+ALTER TABLE `article` ADD COLUMN `deleted_at` timestamp NULL DEFAULT NULL, ADD KEY `key_deleted_at` (`deleted_at`);
+
+ALTER TABLE `article_comment` ADD COLUMN `deleted_at` timestamp NULL DEFAULT NULL, ADD KEY `key_deleted_at` (`deleted_at`);
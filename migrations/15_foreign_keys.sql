@@ -0,0 +1,41 @@
+DELETE FROM `follow`
+WHERE `followed_user_id` NOT IN (SELECT `user_id` FROM `user`)
+OR `following_user_id` NOT IN (SELECT `user_id` FROM `user`);
+
+DELETE FROM `article` WHERE `user_id` NOT IN (SELECT `user_id` FROM `user`);
+
+DELETE FROM `article_comment`
+WHERE `article_id` NOT IN (SELECT `article_id` FROM `article`)
+OR `user_id` NOT IN (SELECT `user_id` FROM `user`);
+
+DELETE FROM `article_favorite`
+WHERE `article_id` NOT IN (SELECT `article_id` FROM `article`)
+OR `user_id` NOT IN (SELECT `user_id` FROM `user`);
+
+DELETE FROM `article_revision`
+WHERE `article_id` NOT IN (SELECT `article_id` FROM `article`)
+OR `user_id` NOT IN (SELECT `user_id` FROM `user`);
+
+DELETE FROM `slug_history` WHERE `article_id` NOT IN (SELECT `article_id` FROM `article`);
+
+ALTER TABLE `follow`
+  ADD CONSTRAINT `fk_follow_followed_user` FOREIGN KEY (`followed_user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_follow_following_user` FOREIGN KEY (`following_user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article`
+  ADD CONSTRAINT `fk_article_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article_comment`
+  ADD CONSTRAINT `fk_article_comment_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_article_comment_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article_favorite`
+  ADD CONSTRAINT `fk_article_favorite_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_article_favorite_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article_revision`
+  ADD CONSTRAINT `fk_article_revision_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_article_revision_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `slug_history`
+  ADD CONSTRAINT `fk_slug_history_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE;
@@ -0,0 +1,111 @@
+ALTER TABLE `follow`
+  DROP FOREIGN KEY `fk_follow_followed_user`,
+  DROP FOREIGN KEY `fk_follow_following_user`;
+
+ALTER TABLE `article`
+  DROP FOREIGN KEY `fk_article_user`;
+
+ALTER TABLE `article_comment`
+  DROP FOREIGN KEY `fk_article_comment_article`,
+  DROP FOREIGN KEY `fk_article_comment_user`;
+
+ALTER TABLE `article_favorite`
+  DROP FOREIGN KEY `fk_article_favorite_article`,
+  DROP FOREIGN KEY `fk_article_favorite_user`;
+
+ALTER TABLE `article_revision`
+  DROP FOREIGN KEY `fk_article_revision_article`,
+  DROP FOREIGN KEY `fk_article_revision_user`;
+
+ALTER TABLE `slug_history`
+  DROP FOREIGN KEY `fk_slug_history_article`;
+
+-- Go through varbinary so the text of each UUID survives until it is converted.
+
+ALTER TABLE `user`
+  MODIFY `user_id` varbinary(36) NOT NULL;
+
+UPDATE `user` SET `user_id` = UUID_TO_BIN(`user_id`);
+
+ALTER TABLE `user`
+  MODIFY `user_id` binary(16) NOT NULL;
+
+ALTER TABLE `follow`
+  MODIFY `followed_user_id` varbinary(36) NOT NULL,
+  MODIFY `following_user_id` varbinary(36) NOT NULL;
+
+UPDATE `follow` SET `followed_user_id` = UUID_TO_BIN(`followed_user_id`), `following_user_id` = UUID_TO_BIN(`following_user_id`);
+
+ALTER TABLE `follow`
+  MODIFY `followed_user_id` binary(16) NOT NULL,
+  MODIFY `following_user_id` binary(16) NOT NULL;
+
+ALTER TABLE `article`
+  MODIFY `article_id` varbinary(36) NOT NULL,
+  MODIFY `user_id` varbinary(36) NOT NULL;
+
+UPDATE `article` SET `article_id` = UUID_TO_BIN(`article_id`), `user_id` = UUID_TO_BIN(`user_id`);
+
+ALTER TABLE `article`
+  MODIFY `article_id` binary(16) NOT NULL,
+  MODIFY `user_id` binary(16) NOT NULL;
+
+ALTER TABLE `article_favorite`
+  MODIFY `article_id` varbinary(36) NOT NULL,
+  MODIFY `user_id` varbinary(36) NOT NULL;
+
+UPDATE `article_favorite` SET `article_id` = UUID_TO_BIN(`article_id`), `user_id` = UUID_TO_BIN(`user_id`);
+
+ALTER TABLE `article_favorite`
+  MODIFY `article_id` binary(16) NOT NULL,
+  MODIFY `user_id` binary(16) NOT NULL;
+
+ALTER TABLE `article_comment`
+  MODIFY `article_id` varbinary(36) NOT NULL,
+  MODIFY `user_id` varbinary(36) NOT NULL;
+
+UPDATE `article_comment` SET `article_id` = UUID_TO_BIN(`article_id`), `user_id` = UUID_TO_BIN(`user_id`);
+
+ALTER TABLE `article_comment`
+  MODIFY `article_id` binary(16) NOT NULL,
+  MODIFY `user_id` binary(16) NOT NULL;
+
+ALTER TABLE `article_revision`
+  MODIFY `article_id` varbinary(36) NOT NULL,
+  MODIFY `user_id` varbinary(36) NOT NULL;
+
+UPDATE `article_revision` SET `article_id` = UUID_TO_BIN(`article_id`), `user_id` = UUID_TO_BIN(`user_id`);
+
+ALTER TABLE `article_revision`
+  MODIFY `article_id` binary(16) NOT NULL,
+  MODIFY `user_id` binary(16) NOT NULL;
+
+ALTER TABLE `slug_history`
+  MODIFY `article_id` varbinary(36) NOT NULL;
+
+UPDATE `slug_history` SET `article_id` = UUID_TO_BIN(`article_id`);
+
+ALTER TABLE `slug_history`
+  MODIFY `article_id` binary(16) NOT NULL;
+
+ALTER TABLE `follow`
+  ADD CONSTRAINT `fk_follow_followed_user` FOREIGN KEY (`followed_user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_follow_following_user` FOREIGN KEY (`following_user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article`
+  ADD CONSTRAINT `fk_article_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article_comment`
+  ADD CONSTRAINT `fk_article_comment_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_article_comment_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article_favorite`
+  ADD CONSTRAINT `fk_article_favorite_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_article_favorite_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `article_revision`
+  ADD CONSTRAINT `fk_article_revision_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  ADD CONSTRAINT `fk_article_revision_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE;
+
+ALTER TABLE `slug_history`
+  ADD CONSTRAINT `fk_slug_history_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE;
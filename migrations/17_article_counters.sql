@@ -0,0 +1,8 @@
+ALTER TABLE `article`
+  ADD COLUMN `favorites_count` bigint NOT NULL DEFAULT 0,
+  ADD COLUMN `comments_count` bigint NOT NULL DEFAULT 0;
+
+UPDATE `article` SET
+  `favorites_count` = (SELECT COUNT(*) FROM `article_favorite` WHERE `article_favorite`.`article_id` = `article`.`article_id`),
+  `comments_count` = (SELECT COUNT(*) FROM `article_comment` WHERE `article_comment`.`article_id` = `article`.`article_id` AND `article_comment`.`deleted_at` IS NULL),
+  `updated_at` = `updated_at`;
@@ -0,0 +1,6 @@
+-- Replies outlive a parent that is removed for good, e.g. with the account of its author, and
+-- become top-level comments.
+ALTER TABLE `article_comment`
+  ADD COLUMN `parent_comment_id` bigint(20) DEFAULT NULL,
+  ADD COLUMN `depth` int NOT NULL DEFAULT 0,
+  ADD CONSTRAINT `fk_article_comment_parent` FOREIGN KEY (`parent_comment_id`) REFERENCES `article_comment` (`comment_id`) ON DELETE SET NULL;
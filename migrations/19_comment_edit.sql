@@ -0,0 +1,9 @@
+CREATE TABLE `comment_edit` (
+  `edit_id` bigint(20) NOT NULL AUTO_INCREMENT,
+  `comment_id` bigint(20) NOT NULL,
+  `body` text NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`edit_id`),
+  KEY `key_comment_id` (`comment_id`),
+  CONSTRAINT `fk_comment_edit_comment` FOREIGN KEY (`comment_id`) REFERENCES `article_comment` (`comment_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
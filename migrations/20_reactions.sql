@@ -0,0 +1,19 @@
+CREATE TABLE `article_reaction` (
+  `article_id` binary(16) NOT NULL,
+  `user_id` binary(16) NOT NULL,
+  `reaction` varchar(32) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`article_id`,`user_id`,`reaction`),
+  CONSTRAINT `fk_article_reaction_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_article_reaction_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE `comment_reaction` (
+  `comment_id` bigint(20) NOT NULL,
+  `user_id` binary(16) NOT NULL,
+  `reaction` varchar(32) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`comment_id`,`user_id`,`reaction`),
+  CONSTRAINT `fk_comment_reaction_comment` FOREIGN KEY (`comment_id`) REFERENCES `article_comment` (`comment_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_comment_reaction_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,19 @@
+ALTER TABLE `article`
+  ADD COLUMN `comments_locked` tinyint(1) NOT NULL DEFAULT 0;
+
+ALTER TABLE `article_comment`
+  ADD COLUMN `pinned_at` timestamp NULL DEFAULT NULL,
+  ADD KEY `key_article_pinned_at` (`article_id`, `pinned_at`);
+
+CREATE TABLE `moderation_log` (
+  `entry_id` bigint(20) NOT NULL AUTO_INCREMENT,
+  `article_id` binary(16) NOT NULL,
+  `user_id` binary(16) NOT NULL,
+  `action` enum('lock_comments','unlock_comments','pin_comment','unpin_comment','delete_comment') NOT NULL,
+  `comment_id` bigint(20) DEFAULT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`entry_id`),
+  KEY `key_article_id` (`article_id`),
+  CONSTRAINT `fk_moderation_log_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_moderation_log_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
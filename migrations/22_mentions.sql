@@ -0,0 +1,19 @@
+CREATE TABLE `article_mention` (
+  `article_id` binary(16) NOT NULL,
+  `user_id` binary(16) NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`article_id`,`user_id`),
+  KEY `key_user_id` (`user_id`),
+  CONSTRAINT `fk_article_mention_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_article_mention_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE `comment_mention` (
+  `comment_id` bigint(20) NOT NULL,
+  `user_id` binary(16) NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`comment_id`,`user_id`),
+  KEY `key_user_id` (`user_id`),
+  CONSTRAINT `fk_comment_mention_comment` FOREIGN KEY (`comment_id`) REFERENCES `article_comment` (`comment_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_comment_mention_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
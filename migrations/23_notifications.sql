@@ -0,0 +1,24 @@
+CREATE TABLE `notification` (
+  `notification_id` bigint(20) NOT NULL AUTO_INCREMENT,
+  `user_id` binary(16) NOT NULL,
+  `actor_user_id` binary(16) NOT NULL,
+  `kind` enum('follow','favorite','comment','mention') NOT NULL,
+  `article_id` binary(16) DEFAULT NULL,
+  `comment_id` bigint(20) DEFAULT NULL,
+  `read_at` timestamp NULL DEFAULT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`notification_id`),
+  KEY `key_user_read_at` (`user_id`, `read_at`),
+  CONSTRAINT `fk_notification_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_notification_actor` FOREIGN KEY (`actor_user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_notification_article` FOREIGN KEY (`article_id`) REFERENCES `article` (`article_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_notification_comment` FOREIGN KEY (`comment_id`) REFERENCES `article_comment` (`comment_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+-- Every kind of notification is on unless the user turned it off here.
+CREATE TABLE `notification_mute` (
+  `user_id` binary(16) NOT NULL,
+  `kind` enum('follow','favorite','comment','mention') NOT NULL,
+  PRIMARY KEY (`user_id`, `kind`),
+  CONSTRAINT `fk_notification_mute_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,59 @@
+-- A webhook receives the events about its owner, or every event if it is global. Only
+-- admins can create global webhooks.
+CREATE TABLE `webhook` (
+  `webhook_id` binary(16) NOT NULL,
+  `user_id` binary(16) NOT NULL,
+  `url` varchar(2048) NOT NULL,
+  `secret` varchar(64) NOT NULL,
+  `events` json NOT NULL,
+  `global` tinyint(1) NOT NULL DEFAULT 0,
+  `active` tinyint(1) NOT NULL DEFAULT 1,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `updated_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (`webhook_id`),
+  KEY `key_user_id` (`user_id`),
+  CONSTRAINT `fk_webhook_user` FOREIGN KEY (`user_id`) REFERENCES `user` (`user_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+-- Written in the same transaction as the change an event is about, then fanned out into
+-- deliveries by the webhook worker.
+CREATE TABLE `webhook_event` (
+  `event_id` bigint(20) NOT NULL AUTO_INCREMENT,
+  `event` enum('article.created','article.updated','article.deleted','comment.created','user.followed') NOT NULL,
+  -- The user whose webhooks receive the event, besides the global ones.
+  `user_id` binary(16) NOT NULL,
+  `data` json NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `dispatched_at` timestamp NULL DEFAULT NULL,
+  PRIMARY KEY (`event_id`),
+  KEY `key_dispatched_at` (`dispatched_at`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE `webhook_delivery` (
+  `delivery_id` bigint(20) NOT NULL AUTO_INCREMENT,
+  `webhook_id` binary(16) NOT NULL,
+  `event_id` bigint(20) NOT NULL,
+  `status` enum('pending','delivered','failed') NOT NULL DEFAULT 'pending',
+  `attempts` int(11) NOT NULL DEFAULT 0,
+  `next_attempt_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `delivered_at` timestamp NULL DEFAULT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`delivery_id`),
+  KEY `key_status_next_attempt_at` (`status`, `next_attempt_at`),
+  KEY `key_webhook_id` (`webhook_id`),
+  CONSTRAINT `fk_webhook_delivery_webhook` FOREIGN KEY (`webhook_id`) REFERENCES `webhook` (`webhook_id`) ON DELETE CASCADE,
+  CONSTRAINT `fk_webhook_delivery_event` FOREIGN KEY (`event_id`) REFERENCES `webhook_event` (`event_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE `webhook_attempt` (
+  `attempt_id` bigint(20) NOT NULL AUTO_INCREMENT,
+  `delivery_id` bigint(20) NOT NULL,
+  -- `NULL` when no response was received.
+  `status_code` smallint(6) DEFAULT NULL,
+  `error` varchar(1024) DEFAULT NULL,
+  `duration_ms` int(11) NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`attempt_id`),
+  KEY `key_delivery_id` (`delivery_id`),
+  CONSTRAINT `fk_webhook_attempt_delivery` FOREIGN KEY (`delivery_id`) REFERENCES `webhook_delivery` (`delivery_id`) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,16 @@
+-- One index per listing order, each starting with the columns the listing filters on, so
+-- cursor pages are read off the index. InnoDB appends `article_id`, which breaks ties.
+ALTER TABLE `article`
+  ADD KEY `key_listing_created_at` (`status`, `deleted_at`, `created_at`),
+  ADD KEY `key_listing_updated_at` (`status`, `deleted_at`, `updated_at`),
+  ADD KEY `key_listing_favorites_count` (`status`, `deleted_at`, `favorites_count`),
+  ADD KEY `key_listing_comments_count` (`status`, `deleted_at`, `comments_count`),
+  -- The feed, by author.
+  ADD KEY `key_user_listing_created_at` (`user_id`, `status`, `deleted_at`, `created_at`),
+  -- Drafts.
+  ADD KEY `key_user_listing_updated_at` (`user_id`, `status`, `deleted_at`, `updated_at`);
+
+-- Covered by the keys above; `fk_article_user` now uses `key_user_listing_created_at`.
+ALTER TABLE `article`
+  DROP KEY `key_status_created_at`,
+  DROP KEY `key_user_status`;
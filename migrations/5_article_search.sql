@@ -0,0 +1 @@
+ALTER TABLE `article` ADD FULLTEXT KEY `ft_article_search` (`title`, `description`, `body`);
@@ -0,0 +1 @@
+ALTER TABLE `user` ADD FULLTEXT KEY `ft_user_bio` (`bio`);
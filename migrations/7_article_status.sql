@@ -0,0 +1,7 @@
+ALTER TABLE `article`
+  ADD COLUMN `status` enum('draft','published','archived') NOT NULL DEFAULT 'published' AFTER `tag_list`,
+  ADD COLUMN `published_at` timestamp NULL DEFAULT NULL AFTER `status`,
+  ADD KEY `key_status_created_at` (`status`, `created_at`),
+  ADD KEY `key_user_status` (`user_id`, `status`);
+
+UPDATE `article` SET `published_at` = `created_at`, `updated_at` = `updated_at`;
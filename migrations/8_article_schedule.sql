@@ -0,0 +1,3 @@
+ALTER TABLE `article`
+  ADD COLUMN `publish_at` timestamp NULL DEFAULT NULL AFTER `published_at`,
+  ADD KEY `key_status_publish_at` (`status`, `publish_at`);
@@ -0,0 +1,13 @@
+CREATE TABLE `article_revision` (
+  `article_id` varchar(36) NOT NULL,
+  `revision` int NOT NULL,
+  `user_id` varchar(36) NOT NULL,
+  `title` varchar(250) NOT NULL,
+  `description` varchar(500) NOT NULL,
+  `body` longtext NOT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (`article_id`,`revision`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+INSERT INTO `article_revision` (`article_id`, `revision`, `user_id`, `title`, `description`, `body`, `created_at`)
+SELECT `article_id`, 1, `user_id`, `title`, `description`, `body`, `updated_at` FROM `article`;
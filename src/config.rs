@@ -5,4 +5,54 @@ pub struct Config {
 
     #[clap(long, env)]
     pub hmac_key: String,
+
+    /// Days a deleted article or comment stays in the trash before it is purged for good.
+    #[clap(long, env, default_value = "30")]
+    pub trash_retention_days: i64,
+
+    /// Minutes after posting during which a comment can still be edited by its author.
+    #[clap(long, env, default_value = "15")]
+    pub comment_edit_window_minutes: i64,
+
+    /// Comma-separated reactions that can be left on articles and comments.
+    #[clap(long, env, default_value = "👍,❤️,🎉,😄", value_delimiter = ',')]
+    pub reactions: Vec<String>,
+
+    /// How many comments an author can pin to the top of an article's discussion.
+    #[clap(long, env, default_value = "3")]
+    pub max_pinned_comments: i64,
+
+    /// Comma-separated IDs of the users allowed to create global webhooks, which receive the
+    /// events of every user.
+    #[clap(long, env, value_delimiter = ',')]
+    pub admin_user_ids: Vec<uuid::Uuid>,
+
+    /// Attempts at delivering a webhook event before giving up on it.
+    #[clap(long, env, default_value = "8")]
+    pub webhook_max_attempts: i32,
+
+    /// Lets webhooks point at loopback, private and link-local addresses. They are refused
+    /// otherwise, so webhooks can't be used to reach services on the server's network.
+    #[clap(long, env)]
+    pub webhook_allow_private_networks: bool,
+
+    /// Seconds between the pings sent on WebSocket connections.
+    #[clap(long, env, default_value = "20")]
+    pub ws_ping_interval_seconds: u64,
+
+    /// Seconds a WebSocket client may stay silent, pongs included, before it is disconnected.
+    #[clap(long, env, default_value = "60")]
+    pub ws_idle_timeout_seconds: u64,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Run the HTTP server. This is the default.
+    Serve,
+
+    /// Recompute the favorite and comment counters of every article, then exit.
+    RecountArticles,
 }
\ No newline at end of file
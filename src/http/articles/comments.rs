@@ -1,8 +1,33 @@
-use axum::{Router, extract::{Extension, Path}, Json, routing::{get, delete}};
-use futures::TryStreamExt;
+use std::collections::{HashMap, HashSet};
+
+use axum::{Router, extract::{Extension, Path, Query}, Json, routing::{get, put}};
+use sqlx::{MySql, Transaction};
 use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::http::{types::{Timestamptz, DbBool, DbUuid}, events::Topic, notifications::{notify, publish_notifications, NotificationKind}, profiles::Profile, extractor::{MaybeAuthUser, AuthUser}, webhooks::{self, WebhookEvent}, ApiContext, Result, ResultExt, Error};
+
+use super::{
+    adjust_article_counters,
+    mentions,
+    moderation::{record_moderation, ModerationAction},
+    reactions::{summarize_reactions, Reaction},
+    visible_article_id,
+    ArticleSlug,
+};
+
+/// Replies nest at most this many levels deep, counting top-level comments as the first.
+const MAX_COMMENT_DEPTH: i32 = 5;
 
-use crate::http::{types::{Timestamptz, DbBool}, profiles::Profile, extractor::{MaybeAuthUser, AuthUser}, ApiContext, Result, Error};
+/// Body shown in place of a deleted comment that is kept around for its replies.
+const TOMBSTONE_BODY: &str = "[deleted]";
+
+const DEFAULT_COMMENT_LIMIT: i64 = 50;
+
+const MAX_COMMENT_LIMIT: i64 = 100;
+
+/// Replies returned with each thread entry; later ones are left out of the page.
+const MAX_REPLIES_PER_THREAD: i64 = 200;
 
 pub fn router() -> Router {
     Router::new()
@@ -12,37 +37,163 @@ pub fn router() -> Router {
         )
         .route(
             "/api/articles/:slug/comments/:comment_id",
-            delete(delete_comment),
+            put(update_comment).delete(delete_comment),
+        )
+        .route(
+            "/api/articles/:slug/comments/:comment_id/history",
+            get(get_comment_history),
         )
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
-struct CommentBody<T = Comment> {
-    comment: T,
+pub(super) struct CommentBody<T = Comment> {
+    pub comment: T,
+}
+
+/// A new comment, as streamed to the readers of its article and sent to webhooks.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentEvent<'a> {
+    article_slug: &'a str,
+    comment: &'a Comment,
 }
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 struct MultipleCommentsBody {
     comments: Vec<Comment>,
+    /// Number of comments matching `sinceId`, replies included.
+    comments_count: i64,
+    next_cursor: Option<String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct ListCommentsQuery {
+    order: CommentOrder,
+    /// Only return comments posted after this one, e.g. the newest a poller has seen.
+    #[serde(rename = "sinceId")]
+    since_id: Option<i64>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+/// Order of the threads in a page. Replies within a thread are always oldest first.
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum CommentOrder {
+    Oldest,
+    Newest,
+}
+
+impl Default for CommentOrder {
+    fn default() -> Self {
+        Self::Oldest
+    }
+}
+
+impl CommentOrder {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Oldest => "oldest",
+            Self::Newest => "newest",
+        }
+    }
+}
+
+/// Opaque position after the last thread of a page, tied to the order it was taken in.
+struct CommentCursor {
+    order: CommentOrder,
+    comment_id: i64,
+}
+
+impl CommentCursor {
+    fn encode(&self) -> String {
+        base64::encode_config(
+            format!("{}:{}", self.order.as_str(), self.comment_id),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    fn decode(cursor: &str, order: CommentOrder) -> Result<Self> {
+        let invalid = || Error::unprocessable_entity([("cursor", "invalid cursor")]);
+
+        let decoded = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+
+        let (cursor_order, comment_id) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        if cursor_order != order.as_str() {
+            return Err(Error::unprocessable_entity([(
+                "cursor",
+                "cursor was issued for a different order",
+            )]));
+        }
+
+        Ok(Self {
+            order,
+            comment_id: comment_id.parse().map_err(|_| invalid())?,
+        })
+    }
 }
 
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct AddComment {
     body: String,
+    /// The comment this one replies to.
+    parent_id: Option<i64>,
+}
+
+#[derive(serde::Deserialize)]
+struct UpdateComment {
+    body: String,
+}
+
+#[derive(serde::Serialize)]
+struct CommentHistoryBody {
+    edits: Vec<CommentEdit>,
+}
+
+/// A body the comment had before it was edited.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentEdit {
+    body: String,
+    /// When this body was replaced.
+    created_at: Timestamptz,
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
-struct Comment {
+pub(super) struct Comment {
     id: i64,
+    parent_id: Option<i64>,
     created_at: Timestamptz,
     updated_at: Timestamptz,
     body: String,
-    author: Profile,
+    /// `None` for deleted comments.
+    author: Option<Profile>,
+    edited: bool,
+    deleted: bool,
+    /// Pinned comments come first in the comments of an article.
+    pinned: bool,
+    pub(super) reactions: Vec<Reaction>,
+    /// Users mentioned in the body, by username.
+    mentions: Vec<String>,
+    reply_count: usize,
+    replies: Vec<Comment>,
+    /// Set on thread entries with more than `MAX_REPLIES_PER_THREAD` replies, of which only
+    /// the oldest are included.
+    replies_truncated: bool,
 }
 
-struct CommentFromQuery {
+pub(super) struct CommentFromQuery {
     comment_id: i64,
+    parent_comment_id: Option<i64>,
+    edited: DbBool,
+    deleted: DbBool,
+    pinned: DbBool,
     created_at: OffsetDateTime,
     updated_at: OffsetDateTime,
     body: String,
@@ -50,48 +201,228 @@ struct CommentFromQuery {
     author_bio: String,
     author_image: Option<String>,
     following_author: DbBool,
+    reaction_counts: Option<serde_json::Value>,
+    own_reactions: Option<serde_json::Value>,
+    mentions: Option<serde_json::Value>,
 }
 
 impl CommentFromQuery {
-    fn into_comment(self) -> Comment {
+    pub(super) fn into_comment(self, replies: Vec<Comment>) -> Comment {
+        let deleted = bool::from(self.deleted);
+
         Comment {
             id: self.comment_id,
+            parent_id: self.parent_comment_id,
             created_at: Timestamptz(self.created_at),
             updated_at: Timestamptz(self.updated_at),
-            body: self.body,
-            author: Profile {
+            body: if deleted { TOMBSTONE_BODY.to_string() } else { self.body },
+            author: (!deleted).then(|| Profile {
                 username: self.author_username,
                 bio: self.author_bio,
                 image: self.author_image,
                 following: self.following_author,
-            },
+            }),
+            edited: self.edited.into(),
+            deleted,
+            pinned: self.pinned.into(),
+            reactions: summarize_reactions(self.reaction_counts, self.own_reactions),
+            mentions: if deleted { Vec::new() } else { mentions::usernames(self.mentions) },
+            reply_count: replies.len(),
+            replies,
+            replies_truncated: false,
+        }
+    }
+}
+
+/// Attaches `replies`, ordered by creation time, to the thread entries they descend from.
+///
+/// Deleted comments stay in the tree as tombstones while they still have replies, and are
+/// dropped otherwise.
+fn build_threads(entries: Vec<CommentFromQuery>, replies: Vec<CommentFromQuery>) -> Vec<Comment> {
+    let mut by_parent: HashMap<i64, Vec<CommentFromQuery>> = HashMap::new();
+
+    for row in replies {
+        if let Some(parent_id) = row.parent_comment_id {
+            by_parent.entry(parent_id).or_default().push(row);
         }
     }
+
+    entries
+        .into_iter()
+        .filter_map(|row| build_thread(row, &mut by_parent))
+        .collect()
 }
 
+fn build_thread(
+    row: CommentFromQuery,
+    by_parent: &mut HashMap<i64, Vec<CommentFromQuery>>,
+) -> Option<Comment> {
+    let replies: Vec<Comment> = by_parent
+        .remove(&row.comment_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|reply| build_thread(reply, by_parent))
+        .collect();
+
+    if bool::from(row.deleted) && replies.is_empty() {
+        return None;
+    }
+
+    Some(row.into_comment(replies))
+}
+
+/// Pages through the comment threads of an article.
+///
+/// A page is made of thread entries with their replies. Without `sinceId`, the entries are the
+/// top-level comments. With `sinceId`, they are the new comments whose parent is not new
+/// itself, so a client polling with the ID of the newest comment it has seen gets every new
+/// reply exactly once, with `parentId` telling it where to attach.
+///
+/// Pinned comments are left out of the pages and lead the first one instead. Deleted entries
+/// with replies still count toward `limit` when all of those replies were deleted as well, so a
+/// page can come back short of it without being the last.
 async fn get_article_comments(
     maybe_auth_user: MaybeAuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
+    ArticleSlug(slug): ArticleSlug,
+    Query(query): Query<ListCommentsQuery>,
 ) -> Result<Json<MultipleCommentsBody>> {
-    let article_id = sqlx::query_scalar!(
+    let article_id =
+        DbUuid(visible_article_id(&ctx.db, &slug, maybe_auth_user.user_id()).await?);
+
+    let limit = query.limit.unwrap_or(DEFAULT_COMMENT_LIMIT).clamp(1, MAX_COMMENT_LIMIT);
+    let newest_first = query.order == CommentOrder::Newest;
+    let since_id = query.since_id;
+    let after = query
+        .cursor
+        .as_deref()
+        .map(|cursor| CommentCursor::decode(cursor, query.order))
+        .transpose()?
+        .map(|cursor| cursor.comment_id);
+
+    let comments_count = sqlx::query_scalar!(
         r#"
-select article_id from article where slug = ?
+select count(*) from article_comment
+where article_id = ? and deleted_at is null and (? is null or comment_id > ?)
         "#,
-        slug
+        article_id,
+        since_id,
+        since_id
     )
-        .fetch_optional(&ctx.db)
-        .await?
-        .ok_or(Error::NotFound)?;
+        .fetch_one(&ctx.db)
+        .await?;
 
-    let comments: Vec<_> = sqlx::query_as!(
+    let mut entries = sqlx::query_as!(
         CommentFromQuery,
         r#"
 select
     comment.comment_id,
+    comment.parent_comment_id,
+    exists(select 1 from comment_edit edit where edit.comment_id = comment.comment_id) `edited!:_`,
+    comment.pinned_at is not null `pinned!:_`,
+    comment.deleted_at is not null `deleted!:_`,
+    comment.created_at,
+    comment.updated_at,
+    comment.body,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from comment_reaction cr
+            where cr.comment_id = comment.comment_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from comment_reaction cr
+        where cr.comment_id = comment.comment_id and cr.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from comment_mention cm
+        inner join user mentioned on mentioned.user_id = cm.user_id
+        where cm.comment_id = comment.comment_id
+    ) `mentions: serde_json::Value`,
+    author.username author_username,
+    author.bio author_bio,
+    author.image author_image,
+    exists(
+        select 1 from follow where followed_user_id = author.user_id and following_user_id = ?
+    ) `following_author!:_`
+from article_comment comment
+inner join user author on author.user_id = comment.user_id
+where comment.article_id = ?
+and (? is null or comment.comment_id > ?)
+and (comment.parent_comment_id is null or comment.parent_comment_id <= ?)
+and comment.pinned_at is null
+-- Deleted comments are only shown for their replies.
+and (
+    comment.deleted_at is null
+    or exists(select 1 from article_comment reply where reply.parent_comment_id = comment.comment_id)
+)
+and (? is null or if(?, comment.comment_id < ?, comment.comment_id > ?))
+order by
+    case when ? then comment.comment_id end desc,
+    comment.comment_id
+limit ?
+        "#,
+        maybe_auth_user.user_id().map(DbUuid),
+        maybe_auth_user.user_id().map(DbUuid),
+        article_id,
+        since_id,
+        since_id,
+        since_id,
+        after,
+        newest_first,
+        after,
+        after,
+        newest_first,
+        limit + 1
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    let has_more = entries.len() as i64 > limit;
+    entries.truncate(limit as usize);
+
+    let next_cursor = entries.last().filter(|_| has_more).map(|last| {
+        CommentCursor {
+            order: query.order,
+            comment_id: last.comment_id,
+        }
+        .encode()
+    });
+
+    if after.is_none() {
+        let pinned = sqlx::query_as!(
+            CommentFromQuery,
+            r#"
+select
+    comment.comment_id,
+    comment.parent_comment_id,
+    exists(select 1 from comment_edit edit where edit.comment_id = comment.comment_id) `edited!:_`,
+    comment.pinned_at is not null `pinned!:_`,
+    comment.deleted_at is not null `deleted!:_`,
     comment.created_at,
     comment.updated_at,
     comment.body,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from comment_reaction cr
+            where cr.comment_id = comment.comment_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from comment_reaction cr
+        where cr.comment_id = comment.comment_id and cr.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from comment_mention cm
+        inner join user mentioned on mentioned.user_id = cm.user_id
+        where cm.comment_id = comment.comment_id
+    ) `mentions: serde_json::Value`,
     author.username author_username,
     author.bio author_bio,
     author.image author_image,
@@ -100,121 +431,515 @@ select
     ) `following_author!:_`
 from article_comment comment
 inner join user author on author.user_id = comment.user_id
-where article_id = ?
-order by created_at
+where comment.article_id = ?
+and comment.pinned_at is not null
+and (? is null or comment.comment_id > ?)
+order by comment.pinned_at
+            "#,
+            maybe_auth_user.user_id().map(DbUuid),
+            maybe_auth_user.user_id().map(DbUuid),
+            article_id,
+            since_id,
+            since_id
+        )
+            .fetch_all(&ctx.db)
+            .await?;
+
+        entries.splice(0..0, pinned);
+    }
+
+    // Replies are always newer than what they reply to, so everything below an entry
+    // already matches `sinceId`.
+    let entry_ids: Vec<i64> = entries.iter().map(|entry| entry.comment_id).collect();
+    let thread_replies =
+        thread_replies(&ctx, maybe_auth_user.user_id(), article_id.0, &entry_ids).await?;
+
+    // Replies come oldest first, so each one's parent was seen before it.
+    let mut thread_of: HashMap<i64, i64> = entry_ids.iter().map(|&id| (id, id)).collect();
+    let mut reply_counts: HashMap<i64, i64> = HashMap::new();
+    let mut truncated = HashSet::new();
+    let mut replies = Vec::new();
+
+    for reply in thread_replies {
+        let thread = match reply.parent_comment_id.and_then(|parent_id| thread_of.get(&parent_id)) {
+            Some(&thread) => thread,
+            None => continue,
+        };
+
+        thread_of.insert(reply.comment_id, thread);
+
+        let count = reply_counts.entry(thread).or_default();
+        *count += 1;
+
+        if *count > MAX_REPLIES_PER_THREAD {
+            truncated.insert(thread);
+        } else {
+            replies.push(reply);
+        }
+    }
+
+    let mut comments = build_threads(entries, replies);
+    for comment in &mut comments {
+        comment.replies_truncated = truncated.contains(&comment.id);
+    }
+
+    Ok(Json(MultipleCommentsBody {
+        comments,
+        comments_count,
+        next_cursor,
+    }))
+}
+
+/// The replies below the thread entries `entry_ids`, oldest first, with up to one more than
+/// `MAX_REPLIES_PER_THREAD` per entry to tell whether its thread was cut. Parents are older
+/// than their replies, so the replies kept always hang together.
+async fn thread_replies(
+    ctx: &ApiContext,
+    user_id: Option<Uuid>,
+    article_id: Uuid,
+    entry_ids: &[i64],
+) -> Result<Vec<CommentFromQuery>> {
+    if entry_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let replies = sqlx::query_as!(
+        CommentFromQuery,
+        r#"
+with recursive thread (entry_id, comment_id) as (
+    select entry.comment_id, entry.comment_id
+    from JSON_TABLE(?, '$[*]' columns (comment_id bigint path '$')) entry_ids
+    inner join article_comment entry on entry.comment_id = entry_ids.comment_id
+    where entry.article_id = ?
+    union all
+    select thread.entry_id, reply.comment_id from article_comment reply
+    inner join thread on reply.parent_comment_id = thread.comment_id
+),
+numbered as (
+    select
+        comment_id,
+        row_number() over (partition by entry_id order by comment_id) position
+    from thread
+    where comment_id <> entry_id
+)
+select
+    comment.comment_id,
+    comment.parent_comment_id,
+    exists(select 1 from comment_edit edit where edit.comment_id = comment.comment_id) `edited!:_`,
+    comment.pinned_at is not null `pinned!:_`,
+    comment.deleted_at is not null `deleted!:_`,
+    comment.created_at,
+    comment.updated_at,
+    comment.body,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from comment_reaction cr
+            where cr.comment_id = comment.comment_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from comment_reaction cr
+        where cr.comment_id = comment.comment_id and cr.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from comment_mention cm
+        inner join user mentioned on mentioned.user_id = cm.user_id
+        where cm.comment_id = comment.comment_id
+    ) `mentions: serde_json::Value`,
+    author.username author_username,
+    author.bio author_bio,
+    author.image author_image,
+    exists(
+        select 1 from follow where followed_user_id = author.user_id and following_user_id = ?
+    ) `following_author!:_`
+from numbered
+inner join article_comment comment using (comment_id)
+inner join user author on author.user_id = comment.user_id
+where numbered.position <= ?
+order by comment.comment_id
         "#,
-        maybe_auth_user.user_id().map(|id| id.to_string()),
-        article_id
+        serde_json::Value::from(entry_ids),
+        DbUuid(article_id),
+        user_id.map(DbUuid),
+        user_id.map(DbUuid),
+        MAX_REPLIES_PER_THREAD + 1
     )
-        .fetch(&ctx.db)
-        .map_ok(CommentFromQuery::into_comment)
-        .try_collect()
+        .fetch_all(&ctx.db)
         .await?;
 
-    Ok(Json(MultipleCommentsBody { comments }))
+    Ok(replies)
 }
 
 async fn add_comment(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
+    ArticleSlug(slug): ArticleSlug,
     req: Json<CommentBody<AddComment>>,
 ) -> Result<Json<CommentBody>> {
     let mut tx = ctx.db.begin().await?;
 
-    let article_id = sqlx::query_scalar!(
+    let article = sqlx::query!(
         r#"
-select article_id from article where slug = ?
+select
+    article_id `article_id: DbUuid`,
+    user_id `user_id: DbUuid`,
+    comments_locked `comments_locked: bool`
+from article
+where slug = ? and (status = 'published' or user_id = ?) and deleted_at is null
         "#,
-        slug
+        slug,
+        DbUuid(auth_user.user_id)
     )
         .fetch_optional(&mut tx)
         .await?
         .ok_or(Error::NotFound)?;
 
+    if article.comments_locked {
+        return Err(Error::Forbidden);
+    }
+
+    let article_id = article.article_id;
+
+    let depth = match req.comment.parent_id {
+        Some(parent_id) => {
+            let parent_depth = sqlx::query_scalar!(
+                r#"
+select depth from article_comment
+where comment_id = ? and article_id = ? and deleted_at is null
+                "#,
+                parent_id,
+                article_id
+            )
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or_else(|| Error::unprocessable_entity([("parentId", "no such comment")]))?;
+
+            if parent_depth + 1 >= MAX_COMMENT_DEPTH {
+                return Err(Error::unprocessable_entity([(
+                    "parentId",
+                    "replies can't be nested any deeper",
+                )]));
+            }
+
+            parent_depth + 1
+        }
+        None => 0,
+    };
+
     let insert_comment_result = sqlx::query_scalar!(
         r#"
-insert into article_comment(article_id, user_id, body) values (?, ?, ?)
+insert into article_comment(article_id, user_id, body, parent_comment_id, depth)
+values (?, ?, ?, ?, ?)
         "#,
         article_id,
-        auth_user.user_id.to_string(),
-        req.comment.body
+        DbUuid(auth_user.user_id),
+        req.comment.body,
+        req.comment.parent_id,
+        depth
     )
         .execute(&mut tx)
+        .await
+        .on_constraint("fk_article_comment_article", |_| Error::NotFound)?;
+
+    adjust_article_counters(&mut tx, article_id.0, 0, 1).await?;
+
+    let comment_id = insert_comment_result.last_insert_id() as i64;
+
+    let mut notified = mentions::record_comment_mentions(
+        &mut tx,
+        article_id.0,
+        comment_id,
+        auth_user.user_id,
+        &req.comment.body,
+    )
         .await?;
 
-    let comment = sqlx::query_as!(
-        CommentFromQuery,
+    notified.extend(
+        notify(
+            &mut tx,
+            article.user_id.0,
+            auth_user.user_id,
+            NotificationKind::Comment,
+            Some(article_id.0),
+            Some(comment_id),
+        )
+            .await?,
+    );
+
+    let comment = comment_by_id(&mut tx, comment_id, auth_user.user_id)
+        .await?
+        .into_comment(Vec::new());
+
+    webhooks::enqueue(
+        &mut tx,
+        WebhookEvent::CommentCreated,
+        article.user_id.0,
+        &CommentEvent {
+            article_slug: &slug,
+            comment: &comment,
+        },
+    )
+        .await?;
+
+    tx.commit().await?;
+
+    ctx.events.publish(
+        Topic::ArticleComments(article_id.0),
+        "comment",
+        &CommentEvent {
+            article_slug: &slug,
+            comment: &comment,
+        },
+    );
+    publish_notifications(&ctx, &notified).await;
+
+    Ok(Json(CommentBody { comment }))
+}
+
+/// Lets the author change the body of a comment within the configured edit window. The
+/// replaced body is kept in `comment_edit`.
+async fn update_comment(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id)): Path<(String, i64)>,
+    req: Json<CommentBody<UpdateComment>>,
+) -> Result<Json<CommentBody>> {
+    let mut tx = ctx.db.begin().await?;
+
+    let comment = sqlx::query!(
         r#"
 select
-    comment.comment_id,
-    comment.created_at,
-    comment.updated_at,
+    comment.article_id `article_id: DbUuid`,
+    comment.user_id `user_id: DbUuid`,
     comment.body,
-    author.username author_username,
-    author.bio author_bio,
-    author.image author_image,
-    0 `following_author!:_`
-    from article_comment comment
-    inner join user author on author.user_id = comment.user_id
-    where comment.comment_id = ?
+    comment.created_at >= current_timestamp - interval ? minute `editable: DbBool`
+from article_comment comment
+inner join article on article.article_id = comment.article_id
+where comment.comment_id = ? and article.slug = ?
+and comment.deleted_at is null and article.deleted_at is null
+for update
         "#,
-        insert_comment_result.last_insert_id()
+        ctx.config.comment_edit_window_minutes,
+        comment_id,
+        slug
     )
-        .fetch_one(&mut tx)
+        .fetch_optional(&mut tx)
         .await?
-        .into_comment();
+        .ok_or(Error::NotFound)?;
+
+    if comment.user_id.0 != auth_user.user_id {
+        return Err(Error::Forbidden);
+    }
+
+    if !bool::from(comment.editable) {
+        return Err(Error::unprocessable_entity([(
+            "comment",
+            format!(
+                "comments can only be edited within {} minutes of posting",
+                ctx.config.comment_edit_window_minutes
+            ),
+        )]));
+    }
+
+    let mut notified = Vec::new();
+
+    if comment.body != req.comment.body {
+        sqlx::query!(
+            r#"
+insert into comment_edit (comment_id, body) values (?, ?)
+            "#,
+            comment_id,
+            comment.body
+        )
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            r#"
+update article_comment set body = ? where comment_id = ?
+            "#,
+            req.comment.body,
+            comment_id
+        )
+            .execute(&mut tx)
+            .await?;
+
+        notified = mentions::record_comment_mentions(
+            &mut tx,
+            comment.article_id.0,
+            comment_id,
+            auth_user.user_id,
+            &req.comment.body,
+        )
+            .await?;
+    }
+
+    let comment = comment_by_id(&mut tx, comment_id, auth_user.user_id).await?.into_comment(Vec::new());
 
     tx.commit().await?;
 
+    publish_notifications(&ctx, &notified).await;
+
     Ok(Json(CommentBody { comment }))
 }
 
+/// The earlier bodies of a comment, newest first. Comments without edits have none, while
+/// unknown and deleted ones, or ones under another article, are not found.
+async fn get_comment_history(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id)): Path<(String, i64)>,
+) -> Result<Json<CommentHistoryBody>> {
+    let article_id = visible_article_id(&ctx.db, &slug, maybe_auth_user.user_id()).await?;
+
+    sqlx::query_scalar!(
+        r#"
+select comment_id from article_comment
+where comment_id = ? and article_id = ? and deleted_at is null
+        "#,
+        comment_id,
+        DbUuid(article_id)
+    )
+        .fetch_optional(&ctx.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let edits = sqlx::query_as!(
+        CommentEdit,
+        r#"
+select body, created_at `created_at: Timestamptz`
+from comment_edit
+where comment_id = ?
+order by edit_id desc
+        "#,
+        comment_id
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Json(CommentHistoryBody { edits }))
+}
+
 async fn delete_comment(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
-    Path((slug, comment_id)): Path<(String, i64)>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id)): Path<(String, i64)>,
 ) -> Result<()> {
     let mut tx = ctx.db.begin().await?;
 
-    let exists = sqlx::query_scalar!(
+    let comment = sqlx::query!(
         r#"
-select exists(
-    select 1 from article_comment comment
-    inner join article on article.article_id = comment.article_id
-    where comment.comment_id = ? and article.slug = ?
-) "!:_"
+select
+    comment.article_id `article_id: DbUuid`,
+    comment.user_id `user_id: DbUuid`,
+    article.user_id `article_author_id: DbUuid`
+from article_comment comment
+inner join article on article.article_id = comment.article_id
+where comment.comment_id = ? and article.slug = ?
+and comment.deleted_at is null and article.deleted_at is null
+for update
         "#,
         comment_id,
         slug
     )
-        .fetch_one(&mut tx)
-        .await?;
-    
-    if exists == 0 {
-        return Err(Error::NotFound);
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    // Authors can remove anything said under their own articles.
+    let moderated = comment.user_id.0 != auth_user.user_id;
+
+    if moderated && comment.article_author_id.0 != auth_user.user_id {
+        return Err(Error::Forbidden);
     }
 
-    let delete_comment_result = sqlx::query_scalar!(
+    sqlx::query!(
         r#"
-delete from article_comment
-    where
-        comment_id = ?
-        and article_id in (select article_id from article where slug = ?)
-        and user_id = ?
+update article_comment
+set deleted_at = current_timestamp, pinned_at = null, updated_at = updated_at
+where comment_id = ?
         "#,
-        comment_id,
-        slug,
-        auth_user.user_id.to_string()
+        comment_id
     )
         .execute(&mut tx)
         .await?;
 
-    tx.commit().await?;
-
-    if delete_comment_result.rows_affected() == 0 {
-        return Err(Error::Forbidden);
+    if moderated {
+        record_moderation(
+            &mut tx,
+            comment.article_id.0,
+            auth_user.user_id,
+            ModerationAction::DeleteComment,
+            Some(comment_id),
+        )
+            .await?;
     }
 
+    adjust_article_counters(&mut tx, comment.article_id.0, 0, -1).await?;
+
+    tx.commit().await?;
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Loads a single comment without its replies, as seen by `viewer`.
+pub(super) async fn comment_by_id(
+    tx: &mut Transaction<'_, MySql>,
+    comment_id: i64,
+    viewer: Uuid,
+) -> Result<CommentFromQuery> {
+    let comment = sqlx::query_as!(
+        CommentFromQuery,
+        r#"
+select
+    comment.comment_id,
+    comment.parent_comment_id,
+    exists(select 1 from comment_edit edit where edit.comment_id = comment.comment_id) `edited!:_`,
+    comment.pinned_at is not null `pinned!:_`,
+    0 `deleted!:_`,
+    comment.created_at,
+    comment.updated_at,
+    comment.body,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from comment_reaction cr
+            where cr.comment_id = comment.comment_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from comment_reaction cr
+        where cr.comment_id = comment.comment_id and cr.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from comment_mention cm
+        inner join user mentioned on mentioned.user_id = cm.user_id
+        where cm.comment_id = comment.comment_id
+    ) `mentions: serde_json::Value`,
+    author.username author_username,
+    author.bio author_bio,
+    author.image author_image,
+    exists(
+        select 1 from follow where followed_user_id = author.user_id and following_user_id = ?
+    ) `following_author!:_`
+    from article_comment comment
+    inner join user author on author.user_id = comment.user_id
+    where comment.comment_id = ?
+        "#,
+        DbUuid(viewer),
+        DbUuid(viewer),
+        comment_id
+    )
+        .fetch_one(&mut *tx)
+        .await?;
+
+    Ok(comment)
+}
@@ -0,0 +1,61 @@
+use sqlx::{MySql, MySqlPool, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::DbUuid, Result};
+
+/// Applies a change in favorites and comments to the counters kept on the article row.
+/// `updated_at` is kept, since it tracks edits by the author.
+///
+/// Call this in the same transaction as the insert or delete it accounts for, so the
+/// counters can't drift from the rows they count.
+pub(super) async fn adjust_article_counters(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    favorites: i64,
+    comments: i64,
+) -> Result<()> {
+    if favorites == 0 && comments == 0 {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+update article
+set
+    favorites_count = favorites_count + ?,
+    comments_count = comments_count + ?,
+    updated_at = updated_at
+where article_id = ?
+        "#,
+        favorites,
+        comments,
+        DbUuid(article_id)
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Recomputes `favorites_count` and `comments_count` of every article from the rows they
+/// count, repairing any drift. Returns the number of articles whose counters changed.
+pub async fn recount_article_counters(db: &MySqlPool) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"
+update article
+set
+    favorites_count = (
+        select count(*) from article_favorite fav where fav.article_id = article.article_id
+    ),
+    comments_count = (
+        select count(*) from article_comment comment
+        where comment.article_id = article.article_id and comment.deleted_at is null
+    ),
+    updated_at = updated_at
+        "#
+    )
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -1,144 +1,734 @@
 use axum::{extract::{Extension, Query}, Json};
-use futures::TryStreamExt;
+use sqlx::{mysql::MySqlArguments, Arguments};
+use uuid::Uuid;
 
-use crate::http::{extractor::{MaybeAuthUser, AuthUser}, ApiContext, types::Timestamptz};
+use crate::http::{extractor::{MaybeAuthUser, AuthUser, WantsBodyHtml}, ApiContext, Error, types::{Timestamptz, DbUuid}};
 
-use super::{Article, ArticleFromQuery, Result};
+use super::{Article, ArticleFromQuery, ArticleStatus, Result};
 
-#[derive(serde::Deserialize, Default)]
+pub(super) const DEFAULT_LIMIT: i64 = 20;
+
+/// Upper bound for `limit`, so a single request can't pull in the whole table.
+pub(super) const MAX_LIMIT: i64 = 100;
+
+#[derive(serde::Deserialize, serde::Serialize, Default, Clone)]
 #[serde(default)]
 pub struct ListArticleQuery {
+    /// Comma-separated list of tags.
     tag: Option<String>,
+    #[serde(rename = "tagMode")]
+    tag_mode: Option<MatchMode>,
+    /// Comma-separated list of usernames; an article matches if it was written by any of them.
     author: Option<String>,
     favorited: Option<String>,
+    sort: Option<ArticleSort>,
+    since: Option<Timestamptz>,
+    until: Option<Timestamptz>,
     limit: Option<i64>,
     offset: Option<i64>,
+    cursor: Option<String>,
 }
 
-#[derive(serde::Deserialize, Default)]
+#[derive(serde::Deserialize, serde::Serialize, Default, Clone)]
 #[serde(default)]
 pub struct FeedArticlesQuery {
     limit: Option<i64>,
     offset: Option<i64>,
+    cursor: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    Any,
+    All,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArticleSort {
+    Newest,
+    Oldest,
+    Favorited,
+    Commented,
+    Updated,
+}
+
+impl ArticleSort {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::Favorited => "favorited",
+            Self::Commented => "commented",
+            Self::Updated => "updated",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "newest" => Some(Self::Newest),
+            "oldest" => Some(Self::Oldest),
+            "favorited" => Some(Self::Favorited),
+            "commented" => Some(Self::Commented),
+            "updated" => Some(Self::Updated),
+            _ => None,
+        }
+    }
+}
+
+/// How a listing is ordered: by `column`, descending unless `ascending`, with ties broken by
+/// `article_id` in the same direction. Migration 25 adds an index for every column and
+/// listing, so pages are read off the index instead of sorting every matching article.
+struct SortKey {
+    column: &'static str,
+    /// Timestamps are kept as Unix time in cursors.
+    timestamp: bool,
+    ascending: bool,
+}
+
+impl ArticleSort {
+    fn key(self) -> SortKey {
+        let (column, timestamp, ascending) = match self {
+            Self::Newest => ("article.created_at", true, false),
+            Self::Oldest => ("article.created_at", true, true),
+            Self::Favorited => ("article.favorites_count", false, false),
+            Self::Commented => ("article.comments_count", false, false),
+            Self::Updated => ("article.updated_at", true, false),
+        };
+
+        SortKey {
+            column,
+            timestamp,
+            ascending,
+        }
+    }
+}
+
+impl ListArticleQuery {
+    fn tags(&self) -> Option<serde_json::Value> {
+        split_list(self.tag.as_deref())
+    }
+
+    fn match_all_tags(&self) -> bool {
+        self.tag_mode == Some(MatchMode::All)
+    }
+
+    fn sort(&self) -> ArticleSort {
+        self.sort.unwrap_or(ArticleSort::Newest)
+    }
+}
+
+/// Splits a comma-separated query parameter into a JSON array we can hand to MySQL's
+/// JSON functions.
+pub(super) fn split_list(list: Option<&str>) -> Option<serde_json::Value> {
+    let items: Vec<_> = list_items(list?)
+        .map(|s| serde_json::Value::String(s.to_string()))
+        .collect();
+
+    if items.is_empty() {
+        None
+    } else {
+        Some(serde_json::Value::Array(items))
+    }
+}
+
+fn list_items(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Resolves the comma-separated usernames of an `author` parameter to user IDs, leaving out
+/// names nobody has. `None` if the parameter names no one, i.e. doesn't filter.
+pub(super) async fn author_ids(
+    ctx: &ApiContext,
+    authors: Option<&str>,
+) -> Result<Option<Vec<Uuid>>> {
+    let mut names = match authors {
+        Some(authors) => list_items(authors).peekable(),
+        None => return Ok(None),
+    };
+
+    if names.peek().is_none() {
+        return Ok(None);
+    }
+
+    let mut user_ids = Vec::new();
+
+    // One lookup per name uses the unique index on `username`, which matching all of them in a
+    // single statement wouldn't.
+    for name in names {
+        let user_id = sqlx::query_scalar!(
+            r#"select user_id `user_id: DbUuid` from user where username = ?"#,
+            name
+        )
+            .fetch_optional(&ctx.db)
+            .await?;
+
+        user_ids.extend(user_id.map(|user_id| user_id.0));
+    }
+
+    Ok(Some(user_ids))
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MultipleArticlesBody {
     articles: Vec<Article>,
-    articles_count: usize,
+    articles_count: i64,
+    links: PageLinks,
 }
 
-pub(in crate::http) async fn list_articles(
-    maybe_auth_user: MaybeAuthUser,
-    ctx: Extension<ApiContext>,
-    query: Query<ListArticleQuery>,
-) -> Result<Json<MultipleArticlesBody>> {
-    let articles: Vec<_> = sqlx::query_as!(
-        ArticleFromQuery,
-        r#"
+#[derive(serde::Serialize)]
+pub struct PageLinks {
+    next: Option<String>,
+    prev: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Next,
+    Prev,
+}
+
+/// Opaque keyset position over `(sort_key, article_id)`.
+///
+/// `sort_key` is the value of the column the listing is ordered by (see [`SortKey`]), as Unix
+/// time for timestamps. A `Next` cursor selects the rows after it in the listing order, a
+/// `Prev` cursor the rows before it.
+struct Cursor {
+    direction: Direction,
+    sort: ArticleSort,
+    sort_key: i64,
+    article_id: Uuid,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let direction = match self.direction {
+            Direction::Next => 'n',
+            Direction::Prev => 'p',
+        };
+
+        base64::encode_config(
+            format!(
+                "{}:{}:{}:{}",
+                direction,
+                self.sort.as_str(),
+                self.sort_key,
+                self.article_id
+            ),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || Error::unprocessable_entity([("cursor", "invalid cursor")]);
+
+        let decoded = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+
+        let mut parts = decoded.splitn(4, ':');
+
+        let direction = match parts.next() {
+            Some("n") => Direction::Next,
+            Some("p") => Direction::Prev,
+            _ => return Err(invalid()),
+        };
+
+        let sort = parts.next().and_then(ArticleSort::from_str).ok_or_else(invalid)?;
+
+        let sort_key = parts
+            .next()
+            .and_then(|key| key.parse::<i64>().ok())
+            .ok_or_else(invalid)?;
+
+        let article_id = parts
+            .next()
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            direction,
+            sort,
+            sort_key,
+            article_id,
+        })
+    }
+}
+
+/// Paging parameters shared by `list_articles` and `feed_articles`.
+struct Page {
+    sort: ArticleSort,
+    cursor: Option<Cursor>,
+    limit: i64,
+    offset: i64,
+}
+
+impl Page {
+    fn from_query(
+        sort: ArticleSort,
+        cursor: Option<&str>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Self> {
+        if cursor.is_some() && offset.is_some() {
+            return Err(Error::unprocessable_entity([(
+                "offset",
+                "cannot be combined with cursor",
+            )]));
+        }
+
+        let cursor = cursor.map(Cursor::decode).transpose()?;
+
+        if matches!(&cursor, Some(cursor) if cursor.sort != sort) {
+            return Err(Error::unprocessable_entity([(
+                "cursor",
+                "cursor belongs to a different sort order",
+            )]));
+        }
+
+        Ok(Self {
+            sort,
+            cursor,
+            limit: limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            offset: offset.unwrap_or(0).max(0),
+        })
+    }
+
+    fn backwards(&self) -> bool {
+        matches!(&self.cursor, Some(cursor) if cursor.direction == Direction::Prev)
+    }
+
+    /// Queries fetch one row more than `limit` so we can tell whether another page exists.
+    fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// Fetches this page of the listing made of the `from` and `where` clauses in `filter`,
+    /// whose placeholders are bound by `args`, as seen by `user_id`.
+    ///
+    /// The page is found by a query built for the listing's sort: its keyset condition and
+    /// `order by` compare the sort column itself, so MySQL can use the matching index. The
+    /// articles on it are then loaded by primary key. `filter` must only ever be made of
+    /// constant SQL.
+    async fn fetch(
+        &self,
+        ctx: &ApiContext,
+        user_id: Option<Uuid>,
+        filter: &str,
+        mut args: MySqlArguments,
+    ) -> Result<Vec<ArticleFromQuery>> {
+        let key = self.sort.key();
+
+        // Backwards pages walk the listing in reverse, starting at the cursor.
+        let (compare, order) = if key.ascending == self.backwards() {
+            ("<", "desc")
+        } else {
+            (">", "asc")
+        };
+
+        let (select_key, cursor_key) = if key.timestamp {
+            (format!("unix_timestamp({})", key.column), "from_unixtime(?)")
+        } else {
+            (key.column.to_string(), "?")
+        };
+
+        let keyset = match &self.cursor {
+            Some(cursor) => {
+                args.add(cursor.sort_key);
+                args.add(cursor.sort_key);
+                args.add(DbUuid(cursor.article_id));
+
+                format!(
+                    "and ({column} {compare} {cursor_key} \
+                     or ({column} = {cursor_key} and article.article_id {compare} ?))",
+                    column = key.column,
+                    compare = compare,
+                    cursor_key = cursor_key,
+                )
+            }
+            None => String::new(),
+        };
+
+        args.add(self.fetch_limit());
+        args.add(self.offset);
+
+        let sql = format!(
+            "select article.article_id, cast({select_key} as signed) {filter} {keyset} \
+             order by {column} {order}, article.article_id {order} limit ? offset ?",
+            select_key = select_key,
+            filter = filter,
+            keyset = keyset,
+            column = key.column,
+            order = order,
+        );
+
+        let keys: Vec<(DbUuid, i64)> = sqlx::query_as_with(&sql, args).fetch_all(&ctx.db).await?;
+
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys = serde_json::Value::Array(
+            keys.into_iter()
+                .map(|(article_id, sort_key)| {
+                    serde_json::json!({ "id": article_id.0.to_string(), "key": sort_key })
+                })
+                .collect(),
+        );
+
+        // Joining from the page onto the primary key looks up only the articles on it.
+        let rows = sqlx::query_as!(
+            ArticleFromQuery,
+            r#"
 select
+    article.article_id `article_id: DbUuid`,
+    article.version,
     slug,
     title,
     description,
     body,
+    body_html,
     tag_list,
+    article.status `status: ArticleStatus`,
+    article.published_at `published_at: Timestamptz`,
+    article.publish_at `publish_at: Timestamptz`,
     article.created_at `created_at: Timestamptz`,
     article.updated_at `updated_at: Timestamptz`,
-    exists(select 1 from article_favorite where user_id = ?) `favorited!:_`,
-    coalesce(
-        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
-        0
-    ) `favorites_count!`,
+    exists(
+        select 1 from article_favorite
+        where article_favorite.article_id = article.article_id and article_favorite.user_id = ?
+    ) `favorited!:_`,
+    article.favorites_count,
+    article.comments_count,
+    article.comments_locked `comments_locked: bool`,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from article_reaction ar
+            where ar.article_id = article.article_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from article_reaction ar
+        where ar.article_id = article.article_id and ar.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from article_mention am
+        inner join user mentioned on mentioned.user_id = am.user_id
+        where am.article_id = article.article_id
+    ) `mentions: serde_json::Value`,
     author.username author_username,
     author.bio author_bio,
     author.image author_image,
-    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = ?) `following_author!:_`
-from article
-inner join user author using (user_id)
-where (
-    ? is null or author.username = ?
-) and (
-    ? is null or JSON_CONTAINS(article.tag_list, JSON_ARRAY(?))
-) and (
-    ? is null or exists(
-        select 1 from user
-        inner join article_favorite af using (user_id)
-        where user.username = ?
-        and af.article_id = article.article_id
-    )
-)
-order by article.created_at desc
-limit ?
-offset ?
-    "#,
-    maybe_auth_user.user_id().map(|id| id.to_string()),
-    maybe_auth_user.user_id().map(|id| id.to_string()),
-    query.author,
-    query.author,
-    query.tag,
-    query.tag,
-    query.favorited,
-    query.favorited,
-    query.limit.unwrap_or(20),
-    query.offset.unwrap_or(0)
-    )
-        .fetch(&ctx.db)
-        .map_ok(ArticleFromQuery::into_article)
-        .try_collect()
+    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = ?) `following_author!:_`,
+    page.sort_key `sort_key!: i64`
+from JSON_TABLE(
+    ?,
+    '$[*]' columns (position for ordinality, article_id char(36) path '$.id', sort_key bigint path '$.key')
+) page
+inner join article on article.article_id = uuid_to_bin(page.article_id)
+inner join user author on author.user_id = article.user_id
+order by page.position
+            "#,
+            user_id.map(DbUuid),
+            user_id.map(DbUuid),
+            user_id.map(DbUuid),
+            keys
+        )
+            .fetch_all(&ctx.db)
+            .await?;
+
+        Ok(rows)
+    }
+
+    /// Turns the rows fetched for this page into articles plus the cursors of the
+    /// neighbouring pages.
+    fn finish(
+        &self,
+        mut rows: Vec<ArticleFromQuery>,
+        wants_body_html: WantsBodyHtml,
+    ) -> (Vec<Article>, Option<Cursor>, Option<Cursor>) {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+
+        // Backwards pages are fetched in reverse order.
+        if self.backwards() {
+            rows.reverse();
+        }
+
+        let (has_next, has_prev) = if self.backwards() {
+            (true, has_more)
+        } else {
+            (has_more, self.cursor.is_some() || self.offset > 0)
+        };
+
+        let cursor_at = |row: &ArticleFromQuery, direction| Cursor {
+            direction,
+            sort: self.sort,
+            sort_key: row.sort_key,
+            article_id: row.article_id.0,
+        };
+
+        let next = rows
+            .last()
+            .filter(|_| has_next)
+            .map(|row| cursor_at(row, Direction::Next));
+        let prev = rows
+            .first()
+            .filter(|_| has_prev)
+            .map(|row| cursor_at(row, Direction::Prev));
+
+        let articles = rows
+            .into_iter()
+            .map(|row| row.into_article().with_body_html(wants_body_html))
+            .collect();
+
+        (articles, next, prev)
+    }
+}
+
+/// The `from` and `where` clauses selecting the articles `query` lists, with the arguments
+/// for their placeholders.
+fn list_filter(query: &ListArticleQuery, author_ids: Option<&[Uuid]>) -> (String, MySqlArguments) {
+    let mut filter = String::from(
+        "from article \
+         where article.status = 'published' \
+         and article.deleted_at is null",
+    );
+    let mut args = MySqlArguments::default();
+
+    match author_ids {
+        // None of the authors exist.
+        Some([]) => filter.push_str(" and false"),
+        Some(author_ids) => {
+            filter.push_str(&format!(
+                " and article.user_id in ({})",
+                vec!["?"; author_ids.len()].join(", ")
+            ));
+
+            for &user_id in author_ids {
+                args.add(DbUuid(user_id));
+            }
+        }
+        None => (),
+    }
+
+    if let Some(tags) = query.tags() {
+        filter.push_str(if query.match_all_tags() {
+            " and JSON_CONTAINS(article.tag_list, ?)"
+        } else {
+            " and JSON_OVERLAPS(article.tag_list, ?)"
+        });
+        args.add(tags);
+    }
+
+    if let Some(favorited) = &query.favorited {
+        filter.push_str(
+            " and exists(\
+                select 1 from user \
+                inner join article_favorite af using (user_id) \
+                where user.username = ? \
+                and af.article_id = article.article_id\
+            )",
+        );
+        args.add(favorited.clone());
+    }
+
+    if let Some(since) = &query.since {
+        filter.push_str(" and article.created_at >= ?");
+        args.add(since.0);
+    }
+
+    if let Some(until) = &query.until {
+        filter.push_str(" and article.created_at < ?");
+        args.add(until.0);
+    }
+
+    (filter, args)
+}
+
+fn page_link<Q: serde::Serialize>(path: &str, query: &Q) -> Result<String> {
+    let query = serde_urlencoded::to_string(query)
+        .map_err(|e| anyhow::anyhow!("failed to encode page link: {}", e))?;
+
+    Ok(format!("{}?{}", path, query))
+}
+
+pub(in crate::http) async fn list_articles(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    query: Query<ListArticleQuery>,
+) -> Result<Json<MultipleArticlesBody>> {
+    let page = Page::from_query(
+        query.sort(),
+        query.cursor.as_deref(),
+        query.limit,
+        query.offset,
+    )?;
+
+    let author_ids = author_ids(&ctx, query.author.as_deref()).await?;
+    let (filter, args) = list_filter(&query, author_ids.as_deref());
+
+    let articles_count: i64 =
+        sqlx::query_scalar_with(&format!("select count(*) {}", filter), args.clone())
+            .fetch_one(&ctx.db)
+            .await?;
+
+    let rows = page
+        .fetch(&ctx, maybe_auth_user.user_id(), &filter, args)
         .await?;
 
+    let (articles, next, prev) = page.finish(rows, wants_body_html);
+
+    let link = |cursor: Cursor| {
+        page_link(
+            "/api/articles",
+            &ListArticleQuery {
+                cursor: Some(cursor.encode()),
+                offset: None,
+                limit: Some(page.limit),
+                ..query.0.clone()
+            },
+        )
+    };
+
     Ok(Json(MultipleArticlesBody {
-        articles_count: articles.len(),
         articles,
+        articles_count,
+        links: PageLinks {
+            next: next.map(link).transpose()?,
+            prev: prev.map(link).transpose()?,
+        },
     }))
 }
 
 pub(in crate::http) async fn feed_articles(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
     query: Query<FeedArticlesQuery>,
 ) -> Result<Json<MultipleArticlesBody>> {
-    let articles: Vec<_> = sqlx::query_as!(
-        ArticleFromQuery,
+    let page = Page::from_query(
+        ArticleSort::Newest,
+        query.cursor.as_deref(),
+        query.limit,
+        query.offset,
+    )?;
+
+    let articles_count = sqlx::query_scalar!(
         r#"
-select
-    slug,
-    title,
-    description,
-    body,
-    tag_list,
-    article.created_at `created_at: Timestamptz`,
-    article.updated_at `updated_at: Timestamptz`,
-    exists(select 1 from article_favorite where user_id = ?) `favorited!:_`,
-    coalesce(
-        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
-        0
-    ) `favorites_count!`,
-    author.username author_username,
-    author.bio author_bio,
-    author.image author_image,
-    1 `following_author!:_`
+select count(*)
 from follow
 inner join article on followed_user_id = article.user_id
-inner join user author using (user_id)
 where following_user_id = ?
-order by article.created_at desc
-limit ?
-offset ?
+and article.status = 'published'
+and article.deleted_at is null
+        "#,
+        DbUuid(auth_user.user_id)
+    )
+        .fetch_one(&ctx.db)
+        .await?;
+
+    let mut args = MySqlArguments::default();
+    args.add(DbUuid(auth_user.user_id));
+
+    let rows = page
+        .fetch(
+            &ctx,
+            Some(auth_user.user_id),
+            "from follow \
+             inner join article on article.user_id = follow.followed_user_id \
+             where follow.following_user_id = ? \
+             and article.status = 'published' \
+             and article.deleted_at is null",
+            args,
+        )
+        .await?;
+
+    let (articles, next, prev) = page.finish(rows, wants_body_html);
+
+    let link = |cursor: Cursor| {
+        page_link(
+            "/api/articles/feed",
+            &FeedArticlesQuery {
+                cursor: Some(cursor.encode()),
+                offset: None,
+                limit: Some(page.limit),
+            },
+        )
+    };
+
+    Ok(Json(MultipleArticlesBody {
+        articles,
+        articles_count,
+        links: PageLinks {
+            next: next.map(link).transpose()?,
+            prev: prev.map(link).transpose()?,
+        },
+    }))
+}
+
+/// Lists the current user's drafts, most recently edited first.
+pub(in crate::http) async fn list_drafts(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    query: Query<FeedArticlesQuery>,
+) -> Result<Json<MultipleArticlesBody>> {
+    let page = Page::from_query(
+        ArticleSort::Updated,
+        query.cursor.as_deref(),
+        query.limit,
+        query.offset,
+    )?;
+
+    let articles_count = sqlx::query_scalar!(
+        r#"
+select count(*) from article where user_id = ? and status = 'draft' and deleted_at is null
         "#,
-        auth_user.user_id.to_string(),
-        auth_user.user_id.to_string(),
-        query.limit.unwrap_or(20),
-        query.offset.unwrap_or(0)
+        DbUuid(auth_user.user_id)
     )
-        .fetch(&ctx.db)
-        .map_ok(ArticleFromQuery::into_article)
-        .try_collect()
+        .fetch_one(&ctx.db)
         .await?;
 
+    let mut args = MySqlArguments::default();
+    args.add(DbUuid(auth_user.user_id));
+
+    let rows = page
+        .fetch(
+            &ctx,
+            Some(auth_user.user_id),
+            "from article \
+             where article.user_id = ? \
+             and article.status = 'draft' \
+             and article.deleted_at is null",
+            args,
+        )
+        .await?;
+
+    let (articles, next, prev) = page.finish(rows, wants_body_html);
+
+    let link = |cursor: Cursor| {
+        page_link(
+            "/api/user/drafts",
+            &FeedArticlesQuery {
+                cursor: Some(cursor.encode()),
+                offset: None,
+                limit: Some(page.limit),
+            },
+        )
+    };
+
     Ok(Json(MultipleArticlesBody {
-        articles_count: articles.len(),
         articles,
+        articles_count,
+        links: PageLinks {
+            next: next.map(link).transpose()?,
+            prev: prev.map(link).transpose()?,
+        },
     }))
-}
\ No newline at end of file
+}
@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use regex::Regex;
+
+/// Renders an article body from CommonMark with the GitHub-flavored extensions (tables,
+/// strikethrough, task lists, footnotes) and passes the result through an allow-list
+/// sanitizer, so it is safe to insert into a page as-is.
+pub(super) fn render(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+
+    sanitizer().clean(&unsafe_html).to_string()
+}
+
+/// Usernames mentioned as `@username` in a body, in order of first appearance.
+///
+/// Code spans and code blocks are skipped, and so is anything glued to a preceding word, so
+/// `@` in `jane@example.com` doesn't count.
+pub(super) fn mentions(markdown: &str) -> Vec<String> {
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+    let mention = MENTION.get_or_init(|| Regex::new(r"(?:^|[^\w@.])@([\w-]{1,50})").unwrap());
+
+    let mut usernames: Vec<String> = Vec::new();
+    let mut in_code_block = false;
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                for captures in mention.captures_iter(&text) {
+                    let username = &captures[1];
+                    if !usernames.iter().any(|seen| seen == username) {
+                        usernames.push(username.to_string());
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    usernames
+}
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+
+        builder
+            // Task list items are rendered as disabled checkboxes.
+            .add_tags(&["input"])
+            .add_tag_attributes("input", &["type", "checked", "disabled"])
+            // Fenced code blocks carry their language as `class="language-*"`.
+            .add_tag_attributes("code", &["class"])
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                ("input", "type") if value != "checkbox" => None,
+                ("code", "class") if !value.starts_with("language-") => None,
+                _ => Some(Cow::Borrowed(value)),
+            });
+
+        builder
+    })
+}
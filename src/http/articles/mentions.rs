@@ -0,0 +1,231 @@
+use axum::{Router, extract::{Extension, Query}, Json, routing::get};
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::{Timestamptz, DbUuid}, extractor::AuthUser, notifications::{notify, NotificationKind}, ApiContext, Result};
+
+use super::listing::{DEFAULT_LIMIT, MAX_LIMIT};
+use super::markdown;
+
+pub fn router() -> Router {
+    Router::new().route("/api/user/mentions", get(list_mentions))
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct ListMentionsQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultipleMentionsBody {
+    mentions: Vec<Mention>,
+}
+
+/// A place the current user was mentioned in: an article, or a comment under it.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Mention {
+    article_slug: String,
+    article_title: String,
+    comment_id: Option<i64>,
+    /// Who wrote the article or comment.
+    author_username: String,
+    created_at: Timestamptz,
+}
+
+/// Reads the `mentions` array selected next to an article or comment.
+pub(super) fn usernames(mentions: Option<serde_json::Value>) -> Vec<String> {
+    mentions
+        .and_then(|mentions| serde_json::from_value(mentions).ok())
+        .unwrap_or_default()
+}
+
+/// The users mentioned in `body`, leaving out names nobody has.
+async fn mentioned_users(tx: &mut Transaction<'_, MySql>, body: &str) -> Result<Vec<Uuid>> {
+    let mut user_ids: Vec<Uuid> = Vec::new();
+
+    // One lookup per name uses the unique index on `username`, which matching all of them in a
+    // single statement wouldn't.
+    for username in markdown::mentions(body) {
+        let user_id = sqlx::query_scalar!(
+            r#"select user_id `user_id: DbUuid` from user where username = ?"#,
+            username
+        )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        // Names differing only in case are the same user.
+        if let Some(user_id) = user_id {
+            if !user_ids.contains(&user_id.0) {
+                user_ids.push(user_id.0);
+            }
+        }
+    }
+
+    Ok(user_ids)
+}
+
+/// Replaces the users mentioned by an article with those in its current `body`, and notifies
+/// the ones it didn't mention before. Returns the IDs of those notifications.
+pub(super) async fn record_article_mentions(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    actor_id: Uuid,
+    body: &str,
+) -> Result<Vec<i64>> {
+    let mentioned = mentioned_users(tx, body).await?;
+
+    let previously = sqlx::query_scalar!(
+        r#"select user_id `user_id: DbUuid` from article_mention where article_id = ?"#,
+        DbUuid(article_id)
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Users still mentioned keep their row, and with it when they were first mentioned.
+    for user_id in &previously {
+        if !mentioned.contains(&user_id.0) {
+            sqlx::query!(
+                r#"delete from article_mention where article_id = ? and user_id = ?"#,
+                DbUuid(article_id),
+                DbUuid(user_id.0)
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    let newly_mentioned = mentioned
+        .into_iter()
+        .filter(|user_id| !previously.iter().any(|previous| previous.0 == *user_id));
+
+    let mut notified = Vec::new();
+
+    for user_id in newly_mentioned {
+        sqlx::query!(
+            r#"
+insert into article_mention (article_id, user_id) values (?, ?)
+            "#,
+            DbUuid(article_id),
+            DbUuid(user_id)
+        )
+            .execute(&mut *tx)
+            .await?;
+
+        notified.extend(
+            notify(tx, user_id, actor_id, NotificationKind::Mention, Some(article_id), None).await?,
+        );
+    }
+
+    Ok(notified)
+}
+
+/// Replaces the users mentioned by a comment with those in its current `body`, and notifies
+/// the ones it didn't mention before. Returns the IDs of those notifications.
+pub(super) async fn record_comment_mentions(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    comment_id: i64,
+    actor_id: Uuid,
+    body: &str,
+) -> Result<Vec<i64>> {
+    let mentioned = mentioned_users(tx, body).await?;
+
+    let previously = sqlx::query_scalar!(
+        r#"select user_id `user_id: DbUuid` from comment_mention where comment_id = ?"#,
+        comment_id
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for user_id in &previously {
+        if !mentioned.contains(&user_id.0) {
+            sqlx::query!(
+                r#"delete from comment_mention where comment_id = ? and user_id = ?"#,
+                comment_id,
+                DbUuid(user_id.0)
+            )
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    let newly_mentioned = mentioned
+        .into_iter()
+        .filter(|user_id| !previously.iter().any(|previous| previous.0 == *user_id));
+
+    let mut notified = Vec::new();
+
+    for user_id in newly_mentioned {
+        sqlx::query!(
+            r#"
+insert into comment_mention (comment_id, user_id) values (?, ?)
+            "#,
+            comment_id,
+            DbUuid(user_id)
+        )
+            .execute(&mut *tx)
+            .await?;
+
+        notified.extend(
+            notify(
+                tx,
+                user_id,
+                actor_id,
+                NotificationKind::Mention,
+                Some(article_id),
+                Some(comment_id),
+            )
+                .await?,
+        );
+    }
+
+    Ok(notified)
+}
+
+/// Where the current user was mentioned, newest first. Drafts and deleted articles or
+/// comments are left out.
+async fn list_mentions(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Query(query): Query<ListMentionsQuery>,
+) -> Result<Json<MultipleMentionsBody>> {
+    let mentions = sqlx::query_as!(
+        Mention,
+        r#"
+select
+    article.slug article_slug,
+    article.title article_title,
+    mentions.comment_id `comment_id?: i64`,
+    author.username author_username,
+    mentions.created_at `created_at!: Timestamptz`
+from (
+    select article_id, null comment_id, created_at
+    from article_mention
+    where user_id = ?
+    union all
+    select comment.article_id, comment.comment_id, mention.created_at
+    from comment_mention mention
+    inner join article_comment comment using (comment_id)
+    where mention.user_id = ? and comment.deleted_at is null
+) mentions
+inner join article on article.article_id = mentions.article_id
+left join article_comment comment on comment.comment_id = mentions.comment_id
+inner join user author on author.user_id = coalesce(comment.user_id, article.user_id)
+where article.status = 'published' and article.deleted_at is null
+order by mentions.created_at desc, mentions.comment_id desc
+limit ? offset ?
+        "#,
+        DbUuid(auth_user.user_id),
+        DbUuid(auth_user.user_id),
+        query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        query.offset.unwrap_or(0).max(0)
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Json(MultipleMentionsBody { mentions }))
+}
@@ -1,16 +1,45 @@
-use std::str::FromStr;
+use std::future::Future;
 
-use anyhow::Context;
-use axum::{Router, extract::{Extension, Path}, Json, routing::{post, get}};
+use axum::{
+    Router,
+    body::{boxed, BoxBody, Bytes, Full, HttpBody},
+    extract::Extension,
+    http::{header, HeaderMap, HeaderValue, Response, StatusCode},
+    response::IntoResponse,
+    Json,
+    routing::{post, get},
+};
 use itertools::Itertools;
-use sqlx::{MySql, Executor};
+use sqlx::{MySql, MySqlPool, Executor, Transaction};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
-use super::{types::{Timestamptz, DbBool}, profiles::Profile, extractor::{AuthUser, MaybeAuthUser}, ApiContext, ResultExt, Error};
+use super::{types::{Timestamptz, DbBool, DbUuid}, events::{EventBus, Topic}, notifications::{notify, publish_notifications, NotificationKind}, profiles::Profile, webhooks::{self, WebhookEvent}, extractor::{AuthUser, MaybeAuthUser, Preconditions, WantsBodyHtml}, ApiContext, ResultExt, Error};
 use super::Result;
 
 mod comments;
+mod counters;
 mod listing;
+mod markdown;
+mod mentions;
+mod moderation;
+mod reactions;
+mod revisions;
+mod scheduler;
+mod search;
+mod slug;
+mod trash;
+
+#[cfg(test)]
+mod tests;
+
+pub(super) use scheduler::spawn_publish_scheduler;
+pub(super) use trash::spawn_trash_purger;
+pub use counters::recount_article_counters;
+
+use counters::adjust_article_counters;
+
+use slug::ArticleSlug;
 
 pub fn router() -> Router {
     Router::new()
@@ -19,6 +48,7 @@ pub fn router() -> Router {
             post(create_article).get(listing::list_articles),
         )
         .route("/api/articles/feed", get(listing::feed_articles))
+        .route("/api/articles/search", get(search::search_articles))
         .route(
             "/api/articles/:slug",
             get(get_article).put(update_article).delete(delete_article),
@@ -27,8 +57,19 @@ pub fn router() -> Router {
             "/api/articles/:slug/favorite",
             post(favorite_article).delete(unfavorite_article),
         )
+        .route(
+            "/api/articles/:slug/publish",
+            post(publish_article).delete(unpublish_article),
+        )
+        .route("/api/articles/:slug/archive", post(archive_article))
+        .route("/api/user/drafts", get(listing::list_drafts))
         .route("/api/tags", get(get_tags))
         .merge(comments::router())
+        .merge(mentions::router())
+        .merge(moderation::router())
+        .merge(reactions::router())
+        .merge(revisions::router())
+        .merge(trash::router())
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -36,6 +77,12 @@ struct ArticleBody<T = Article> {
     article: T,
 }
 
+/// What is left of an article in `article.deleted` webhook events.
+#[derive(serde::Serialize)]
+struct DeletedArticle {
+    slug: String,
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 struct TagsBody {
     tags: Vec<String>,
@@ -47,6 +94,12 @@ struct CreateArticle {
     description: String,
     body: String,
     tag_list: Vec<String>,
+    /// Either `draft` or `published`; articles are published right away if omitted.
+    status: Option<ArticleStatus>,
+    /// Keeps the article as a draft until the scheduler publishes it at this time.
+    publish_at: Option<Timestamptz>,
+    /// Makes `If-Match` mandatory for updates and deletes of this article.
+    require_if_match: Option<bool>,
 }
 
 #[derive(serde::Deserialize)]
@@ -54,52 +107,104 @@ struct UpdateArticle {
     title: Option<String>,
     description: Option<String>,
     body: Option<String>,
+    /// (Re)schedules publication; only allowed while the article is a draft.
+    publish_at: Option<Timestamptz>,
+    require_if_match: Option<bool>,
+}
+
+/// Only `Published` articles show up in listings, the feed and tags; the others are visible
+/// to their author alone.
+#[derive(sqlx::Type, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+enum ArticleStatus {
+    Draft,
+    Published,
+    Archived,
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Article {
+    #[serde(skip)]
+    article_id: Uuid,
+    /// Bumped by every change the author or a moderator makes, see `article_etag`.
+    #[serde(skip)]
+    version: i32,
     slug: String,
     title: String,
     description: String,
     body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_html: Option<String>,
     tag_list: Vec<String>,
+    status: ArticleStatus,
+    published_at: Option<Timestamptz>,
+    publish_at: Option<Timestamptz>,
     created_at: Timestamptz,
     updated_at: Timestamptz,
     favorited: DbBool,
     favorites_count: i64,
+    comments_count: i64,
+    comments_locked: bool,
+    reactions: Vec<reactions::Reaction>,
+    /// Users mentioned in the body, by username.
+    mentions: Vec<String>,
     author: Profile,
 }
 
 struct ArticleFromQuery {
+    article_id: DbUuid,
+    version: i32,
     slug: String,
     title: String,
     description: String,
     body: String,
+    body_html: Option<String>,
     tag_list: serde_json::Value,
+    status: ArticleStatus,
+    published_at: Option<Timestamptz>,
+    publish_at: Option<Timestamptz>,
     created_at: Timestamptz,
     updated_at: Timestamptz,
     favorited: DbBool,
     favorites_count: i64,
+    comments_count: i64,
+    comments_locked: bool,
+    reaction_counts: Option<serde_json::Value>,
+    own_reactions: Option<serde_json::Value>,
+    mentions: Option<serde_json::Value>,
     author_username: String,
     author_bio: String,
     author_image: Option<String>,
     following_author: DbBool,
+    /// Position in the listing order, see `listing::Cursor`. Single-article queries leave it at 0.
+    sort_key: i64,
 }
 
 impl ArticleFromQuery {
     fn into_article(self) -> Article {
         let tag_list = serde_json::from_value::<Vec<String>>(self.tag_list).unwrap_or_default();
         Article {
+            article_id: self.article_id.0,
+            version: self.version,
             slug: self.slug,
             title: self.title,
             description: self.description,
             body: self.body,
+            body_html: self.body_html,
             tag_list,
+            status: self.status,
+            published_at: self.published_at,
+            publish_at: self.publish_at,
             created_at: self.created_at,
             updated_at: self.updated_at,
             favorited: self.favorited,
             favorites_count: self.favorites_count,
+            comments_count: self.comments_count,
+            comments_locked: self.comments_locked,
+            reactions: reactions::summarize_reactions(self.reaction_counts, self.own_reactions),
+            mentions: mentions::usernames(self.mentions),
             author: Profile {
                 username: self.author_username,
                 bio: self.author_bio,
@@ -110,73 +215,404 @@ impl ArticleFromQuery {
     }
 }
 
+impl Article {
+    /// Keeps the rendered body only for clients that asked for it. Rows written before
+    /// `body_html` was introduced are rendered on the fly.
+    fn with_body_html(mut self, wants_body_html: WantsBodyHtml) -> Self {
+        if !wants_body_html.0 {
+            self.body_html = None;
+        } else if self.body_html.is_none() {
+            self.body_html = Some(markdown::render(&self.body));
+        }
+
+        self
+    }
+}
+
+/// Strong entity tag of an article: its ID and `version`, which changes whenever the author or
+/// a moderator changes it. Counters and the viewer's own favorite, follow and reactions are
+/// left out, so favoriting or commenting doesn't fail the author's next `If-Match`, and every
+/// viewer gets the same tag. A copy revalidated with `If-None-Match` may show them out of date.
+fn article_etag(article_id: Uuid, version: i32) -> String {
+    format!("\"{}-{}\"", article_id.to_simple(), version)
+}
+
+fn etag_headers(etag: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, etag);
+    }
+
+    // The representation depends on who is asking and whether `bodyHtml` was asked for.
+    headers.insert(header::VARY, HeaderValue::from_static("Authorization, Accept"));
+
+    headers
+}
+
+impl IntoResponse for ArticleBody {
+    type Body = Full<Bytes>;
+    type BodyError = <Full<Bytes> as HttpBody>::Error;
+
+    fn into_response(self) -> Response<Self::Body> {
+        (
+            etag_headers(&article_etag(self.article.article_id, self.article.version)),
+            Json(self),
+        ).into_response()
+    }
+}
+
 async fn create_article(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
     Json(mut req): Json<ArticleBody<CreateArticle>>,
-) -> Result<Json<ArticleBody>> {
-    let slug = slugify(&req.article.title);
+) -> Result<ArticleBody> {
+    let status = req.article.status.unwrap_or(ArticleStatus::Published);
+
+    if status == ArticleStatus::Archived {
+        return Err(Error::unprocessable_entity([(
+            "status",
+            "new articles must be either draft or published",
+        )]));
+    }
+
+    // A scheduled article stays a draft until the scheduler picks it up.
+    let status = match (&req.article.publish_at, req.article.status) {
+        (Some(_), Some(ArticleStatus::Published)) => {
+            return Err(Error::unprocessable_entity([(
+                "publishAt",
+                "can't schedule an article that is published right away",
+            )]));
+        }
+        (Some(publish_at), _) => {
+            validate_publish_at(publish_at)?;
+            ArticleStatus::Draft
+        }
+        (None, _) => status,
+    };
 
     req.article.tag_list.sort();
-    let tag_list = serde_json::to_value(req.article.tag_list).unwrap_or(serde_json::Value::Array(Vec::new()));
+    let tag_list = serde_json::to_value(&req.article.tag_list).unwrap_or(serde_json::Value::Array(Vec::new()));
+
+    let DbUuid(article_id) = DbUuid::new_ordered();
 
-    let article_id = Uuid::new_v4();
+    let (article, notified) = retry_on_deadlock(|| {
+        insert_article(
+            &ctx,
+            auth_user.user_id,
+            article_id,
+            &req.article,
+            &tag_list,
+            status,
+            wants_body_html,
+        )
+    })
+        .await?;
 
+    if status == ArticleStatus::Published {
+        publish_new_article(&ctx.db, &ctx.events, article_id, auth_user.user_id).await;
+    }
+    publish_notifications(&ctx, &notified).await;
+
+    Ok(ArticleBody { article })
+}
+
+/// The transaction of `create_article`. Returns the new article and the notifications of the
+/// users it mentions.
+async fn insert_article(
+    ctx: &ApiContext,
+    user_id: Uuid,
+    article_id: Uuid,
+    req: &CreateArticle,
+    tag_list: &serde_json::Value,
+    status: ArticleStatus,
+    wants_body_html: WantsBodyHtml,
+) -> Result<(Article, Vec<i64>)> {
     let mut tx = ctx.db.begin().await?;
 
-    sqlx::query!(
-        r#"
-insert into article (article_id, slug, user_id, title, description, body, tag_list)
-        values (?, ?, ?, ?, ?, ?, ?)
-        "#,
-        article_id.to_string(),
-        slug,
-        auth_user.user_id.to_string(),
-        req.article.title,
-        req.article.description,
-        req.article.body,
-        tag_list
-    )
-        .execute(&mut tx)
-        .await
-        .on_constraint("key_slug", |_| {
-            Error::unprocessable_entity([("slug", format!("duplicate article slug: {}", slug))])
-        })?;
+    let mut slug = unique_slug(&mut tx, &req.title, article_id, None).await?;
+    let fallback = fallback_slug(&req.title, article_id);
+
+    loop {
+        let inserted = sqlx::query!(
+            r#"
+insert into article (article_id, slug, user_id, title, description, body, body_html, tag_list, status, published_at, publish_at, require_if_match)
+        values (?, ?, ?, ?, ?, ?, ?, ?, ?, if(? = 'published', current_timestamp, null), ?, ?)
+            "#,
+            DbUuid(article_id),
+            slug,
+            DbUuid(user_id),
+            req.title,
+            req.description,
+            req.body,
+            markdown::render(&req.body),
+            tag_list,
+            status,
+            status,
+            req.publish_at.map(|publish_at| publish_at.0),
+            req.require_if_match.unwrap_or(false)
+        )
+            .execute(&mut tx)
+            .await
+            .on_constraint("key_slug", |_| {
+                Error::unprocessable_entity([("slug", format!("duplicate article slug: {}", slug))])
+            });
 
-    let article = article_by_id(&mut tx, Some(auth_user.user_id), article_id).await?;
+        match inserted {
+            // Another article took the slug since `unique_slug` looked.
+            Err(Error::UnprocessableEntity { .. }) if slug != fallback => slug = fallback.clone(),
+            inserted => {
+                inserted?;
+                break;
+            }
+        }
+    }
+
+    revisions::record_revision(&mut tx, article_id, user_id).await?;
+    let notified = mentions::record_article_mentions(&mut tx, article_id, user_id, &req.body).await?;
+
+    if status == ArticleStatus::Published {
+        enqueue_article_event(&mut tx, WebhookEvent::ArticleCreated, article_id, user_id).await?;
+    }
+
+    let article = article_by_id(&mut tx, Some(user_id), article_id, wants_body_html).await?;
 
     tx.commit().await?;
 
-    Ok(Json(ArticleBody { article }))
+    Ok((article, notified))
+}
+
+fn validate_publish_at(publish_at: &Timestamptz) -> Result<()> {
+    if publish_at.0 <= OffsetDateTime::now_utc() {
+        return Err(Error::unprocessable_entity([("publishAt", "must be in the future")]));
+    }
+
+    Ok(())
 }
 
+/// Longest slug generated from a title, leaving room for a suffix within `slug varchar(255)`.
+const MAX_SLUG_LENGTH: usize = 200;
+
+/// Numeric suffixes (`-2`, `-3`, ...) tried before falling back to the article's short ID.
+const MAX_SLUG_SUFFIX: usize = 10;
+
+/// Transliterates the title to ASCII and keeps letters and digits, so "日本語" becomes
+/// `ri-ben-yu` and "Rust 2024" becomes `rust-2024`.
 fn slugify(string: &str) -> String {
     const QUOTE_CHARS: &[char] = &['\'', '"'];
 
-    string
-        .split(|c: char| !(QUOTE_CHARS.contains(&c) || c.is_alphabetic()))
-        .filter(|s| !s.is_empty())
+    let slug = deunicode::deunicode(string)
+        .split(|c: char| !(QUOTE_CHARS.contains(&c) || c.is_ascii_alphanumeric()))
         .map(|s| {
             let mut s = s.replace(QUOTE_CHARS, "");
             s.make_ascii_lowercase();
             s
         })
-        .join("-")
+        .filter(|s| !s.is_empty())
+        .join("-");
+
+    if slug.len() <= MAX_SLUG_LENGTH {
+        return slug;
+    }
+
+    // Cut at a word boundary where possible; the slug is pure ASCII at this point.
+    let truncated = &slug[..MAX_SLUG_LENGTH];
+    truncated
+        .rfind('-')
+        .map_or(truncated, |boundary| &truncated[..boundary])
+        .to_string()
+}
+
+/// Short, stable identifier derived from the article ID, used to disambiguate slugs.
+///
+/// Taken from the random tail of the ID, since the head of an ordered UUID is a timestamp.
+fn short_id(article_id: Uuid) -> String {
+    let hex = article_id.to_simple().to_string();
+    hex[hex.len() - 8..].to_string()
+}
+
+/// Picks a slug for the title that no other article uses: the plain slug, then `-2`, `-3`, ...,
+/// then the article's short ID as a suffix. Titles without any usable characters get the
+/// short ID alone.
+///
+/// Taken slugs are read without locking, since locking the candidate range takes gap locks
+/// that deadlock concurrent writers. One of them may still claim the same slug first, so
+/// writers fall back to `fallback_slug` when they hit `key_slug`.
+async fn unique_slug(
+    tx: &mut Transaction<'_, MySql>,
+    title: &str,
+    article_id: Uuid,
+    current_slug: Option<&str>,
+) -> Result<String> {
+    let base = slugify(title);
+
+    if base.is_empty() {
+        return Ok(short_id(article_id));
+    }
+
+    let candidates = slug_candidates(&base, article_id);
+
+    // An article keeps its slug as long as the title still maps to it.
+    if let Some(current_slug) = current_slug {
+        if candidates.iter().any(|candidate| candidate == current_slug) {
+            return Ok(current_slug.to_string());
+        }
+    }
+
+    let taken: Vec<String> = sqlx::query_scalar!(
+        r#"
+select slug from article where (slug = ? or slug like ?) and article_id <> ?
+        "#,
+        base,
+        format!("{}-%", base),
+        DbUuid(article_id)
+    )
+        .fetch_all(&mut *tx)
+        .await?;
+
+    first_free_slug(candidates, &taken).ok_or_else(|| {
+        Error::unprocessable_entity([("slug", format!("duplicate article slug: {}", base))])
+    })
+}
+
+/// The slugs `unique_slug` tries for a non-empty `base`, in order.
+fn slug_candidates(base: &str, article_id: Uuid) -> Vec<String> {
+    std::iter::once(base.to_string())
+        .chain((2..=MAX_SLUG_SUFFIX).map(|n| format!("{}-{}", base, n)))
+        .chain(std::iter::once(format!("{}-{}", base, short_id(article_id))))
+        .collect()
+}
+
+fn first_free_slug(candidates: Vec<String>, taken: &[String]) -> Option<String> {
+    candidates
+        .into_iter()
+        .find(|candidate| !taken.contains(candidate))
+}
+
+/// The last candidate of `unique_slug`, which no other article can claim since it ends with
+/// this article's short ID.
+fn fallback_slug(title: &str, article_id: Uuid) -> String {
+    match slugify(title) {
+        base if base.is_empty() => short_id(article_id),
+        base => format!("{}-{}", base, short_id(article_id)),
+    }
+}
+
+/// Moves an article from `old_slug` to `new_slug`, or to `fallback_slug` if another article
+/// claimed `new_slug` since `unique_slug` picked it. Returns the slug the article ended up with.
+async fn rename_article(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    title: &str,
+    old_slug: &str,
+    mut new_slug: String,
+) -> Result<String> {
+    if new_slug == old_slug {
+        return Ok(new_slug);
+    }
+
+    let fallback = fallback_slug(title, article_id);
+
+    loop {
+        let renamed = sqlx::query!(
+            r#"
+update article set slug = ? where article_id = ?
+            "#,
+            new_slug,
+            DbUuid(article_id)
+        )
+            .execute(&mut *tx)
+            .await
+            .on_constraint("key_slug", |_| {
+                Error::unprocessable_entity([(
+                    "slug",
+                    format!("duplicate article slug: {}", new_slug),
+                )])
+            });
+
+        match renamed {
+            Err(Error::UnprocessableEntity { .. }) if new_slug != fallback => {
+                new_slug = fallback.clone()
+            }
+            renamed => {
+                renamed?;
+                break;
+            }
+        }
+    }
+
+    slug::record_slug_change(tx, article_id, old_slug, &new_slug).await?;
+
+    Ok(new_slug)
+}
+
+/// Attempts at a transaction before giving up on deadlocks.
+const MAX_DEADLOCK_ATTEMPTS: u32 = 3;
+
+/// Runs the transaction `f` again when MySQL rolled it back to break a deadlock, which can
+/// happen when concurrent writers insert the same slug.
+async fn retry_on_deadlock<T, F, Fut>(mut f: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = 1;
+
+    loop {
+        match f().await {
+            Err(e) if e.is_deadlock() && attempts < MAX_DEADLOCK_ATTEMPTS => attempts += 1,
+            result => return result,
+        }
+    }
 }
 
 async fn update_article(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
+    preconditions: Preconditions,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
     Json(req): Json<ArticleBody<UpdateArticle>>,
-) -> Result<Json<ArticleBody>> {
-    let mut tx = ctx.db.begin().await?;
+) -> Result<ArticleBody> {
+    let (article, notified) = retry_on_deadlock(|| {
+        apply_article_update(
+            &ctx,
+            auth_user.user_id,
+            &preconditions,
+            wants_body_html,
+            &slug,
+            &req.article,
+        )
+    })
+        .await?;
 
-    let new_slug = req.article.title.as_deref().map(slugify);
+    publish_notifications(&ctx, &notified).await;
+
+    Ok(ArticleBody { article })
+}
+
+/// The transaction of `update_article`. Returns the updated article and the notifications of
+/// newly mentioned users.
+async fn apply_article_update(
+    ctx: &ApiContext,
+    user_id: Uuid,
+    preconditions: &Preconditions,
+    wants_body_html: WantsBodyHtml,
+    slug: &str,
+    req: &UpdateArticle,
+) -> Result<(Article, Vec<i64>)> {
+    let mut tx = ctx.db.begin().await?;
 
     let article_meta = sqlx::query!(
         r#"
-select article_id, user_id from article where slug = ? for update
+select
+    article_id `article_id: DbUuid`,
+    user_id `user_id: DbUuid`,
+    status `status: ArticleStatus`,
+    version,
+    require_if_match `require_if_match: bool`
+from article where slug = ? and deleted_at is null for update
         "#,
         slug
     )
@@ -184,53 +620,88 @@ select article_id, user_id from article where slug = ? for update
         .await?
         .ok_or(Error::NotFound)?;
 
-    if article_meta.user_id != auth_user.user_id.to_string() {
+    if article_meta.user_id.0 != user_id {
         return Err(Error::Forbidden);
     }
 
+    preconditions.check_if_match(
+        &article_etag(article_meta.article_id.0, article_meta.version),
+        article_meta.require_if_match,
+    )?;
+
+    if let Some(publish_at) = &req.publish_at {
+        if article_meta.status != ArticleStatus::Draft {
+            return Err(Error::unprocessable_entity([(
+                "publishAt",
+                "only drafts can be scheduled",
+            )]));
+        }
+
+        validate_publish_at(publish_at)?;
+    }
+
+    if let Some(title) = &req.title {
+        let new_slug = unique_slug(&mut tx, title, article_meta.article_id.0, Some(slug)).await?;
+        rename_article(&mut tx, article_meta.article_id.0, title, slug, new_slug).await?;
+    }
+
     sqlx::query!(
         r#"
 update article
 set
-    slug = coalesce(?, slug),
     title = coalesce(?, title),
     description = coalesce(?, description),
-    body = coalesce(?, body)
+    body = coalesce(?, body),
+    body_html = coalesce(?, body_html),
+    publish_at = coalesce(?, publish_at),
+    require_if_match = coalesce(?, require_if_match),
+    version = version + 1
 where article_id = ?
         "#,
-        new_slug,
-        req.article.title,
-        req.article.description,
-        req.article.body,
+        req.title,
+        req.description,
+        req.body,
+        req.body.as_deref().map(markdown::render),
+        req.publish_at.map(|publish_at| publish_at.0),
+        req.require_if_match,
         article_meta.article_id
     )
         .execute(&mut tx)
-        .await
-        .on_constraint("key_slug", |_| {
-            Error::unprocessable_entity([(
-                "slug",
-                format!("duplicate article slug: {}", new_slug.unwrap()),
-            )])
-        })?;
+        .await?;
+
+    revisions::record_revision(&mut tx, article_meta.article_id.0, user_id).await?;
+
+    let notified = match &req.body {
+        Some(body) => {
+            mentions::record_article_mentions(&mut tx, article_meta.article_id.0, user_id, body)
+                .await?
+        }
+        None => Vec::new(),
+    };
+
+    enqueue_article_event(&mut tx, WebhookEvent::ArticleUpdated, article_meta.article_id.0, user_id)
+        .await?;
 
-    let article_id = Uuid::from_str(&article_meta.article_id).context("invalid uuid string")?;
-    let article = article_by_id(&mut tx, Some(auth_user.user_id), article_id).await?;
+    let article =
+        article_by_id(&mut tx, Some(user_id), article_meta.article_id.0, wants_body_html).await?;
 
     tx.commit().await?;
 
-    Ok(Json(ArticleBody { article }))
+    Ok((article, notified))
 }
 
 async fn delete_article(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
+    preconditions: Preconditions,
+    ArticleSlug(slug): ArticleSlug,
 ) -> Result<()> {
     let mut tx = ctx.db.begin().await?;
 
     let article_meta = sqlx::query!(
         r#"
-select article_id, user_id from article where slug = ? for update
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid`, version, require_if_match `require_if_match: bool`
+from article where slug = ? and deleted_at is null for update
         "#,
         slug
     )
@@ -238,19 +709,34 @@ select article_id, user_id from article where slug = ? for update
         .await?
         .ok_or(Error::NotFound)?;
 
-    if article_meta.user_id != auth_user.user_id.to_string() {
+    if article_meta.user_id.0 != auth_user.user_id {
         return Err(Error::Forbidden);
     }
 
+    preconditions.check_if_match(
+        &article_etag(article_meta.article_id.0, article_meta.version),
+        article_meta.require_if_match,
+    )?;
+
     sqlx::query!(
         r#"
-delete from article where article_id = ?
+update article set deleted_at = current_timestamp, version = version + 1 where article_id = ?
         "#,
         article_meta.article_id
     )
         .execute(&mut tx)
         .await?;
 
+    webhooks::enqueue(
+        &mut tx,
+        WebhookEvent::ArticleDeleted,
+        auth_user.user_id,
+        &ArticleBody {
+            article: DeletedArticle { slug },
+        },
+    )
+        .await?;
+
     tx.commit().await?;
 
     Ok(())
@@ -259,157 +745,417 @@ delete from article where article_id = ?
 async fn get_article(
     maybe_auth_user: MaybeAuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
-) -> Result<Json<ArticleBody>> {
+    preconditions: Preconditions,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<Response<BoxBody>> {
     let article = sqlx::query_as!(
         ArticleFromQuery,
         r#"
 select
+    article.article_id `article_id: DbUuid`,
+    article.version,
     article.slug,
     article.title,
     article.description,
     article.body,
+    article.body_html,
     article.tag_list,
+    article.status `status: ArticleStatus`,
+    article.published_at `published_at: Timestamptz`,
+    article.publish_at `publish_at: Timestamptz`,
     article.created_at `created_at: Timestamptz`,
     article.updated_at `updated_at: Timestamptz`,
-    exists(select 1 from article_favorite where user_id = ?) `favorited!:_`,
-    coalesce(
-        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
-        0
-    ) `favorites_count!`,
+    exists(
+        select 1 from article_favorite
+        where article_favorite.article_id = article.article_id and article_favorite.user_id = ?
+    ) `favorited!:_`,
+    article.favorites_count,
+    article.comments_count,
+    article.comments_locked `comments_locked: bool`,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from article_reaction ar
+            where ar.article_id = article.article_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from article_reaction ar
+        where ar.article_id = article.article_id and ar.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from article_mention am
+        inner join user mentioned on mentioned.user_id = am.user_id
+        where am.article_id = article.article_id
+    ) `mentions: serde_json::Value`,
     user.username author_username,
     user.bio author_bio,
     user.image author_image,
-    0 `following_author:_`
+    0 `following_author:_`,
+    0 `sort_key!: i64`
 from article
 inner join user using (user_id)
 where article.slug = ?
+and (article.status = 'published' or article.user_id = ?)
+and article.deleted_at is null
         "#,
-        maybe_auth_user.user_id().map(|id| id.to_string()),
-        slug
+        maybe_auth_user.user_id().map(DbUuid),
+        maybe_auth_user.user_id().map(DbUuid),
+        slug,
+        maybe_auth_user.user_id().map(DbUuid)
     )
         .fetch_optional(&ctx.db)
         .await?
         .ok_or(Error::NotFound)?
         .into_article();
 
-    Ok(Json(ArticleBody { article }))
+    let article = article.with_body_html(wants_body_html);
+    let etag = article_etag(article.article_id, article.version);
+
+    if preconditions.is_not_modified(&etag) {
+        return Ok((StatusCode::NOT_MODIFIED, etag_headers(&etag))
+            .into_response()
+            .map(boxed));
+    }
+
+    Ok(ArticleBody { article }.into_response().map(boxed))
 }
 
 async fn favorite_article(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
-) -> Result<Json<ArticleBody>> {
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
     let mut tx = ctx.db.begin().await?;
 
-    let article_id = sqlx::query_scalar!(
+    let article_meta = sqlx::query!(
         r#"
-select article_id from article where slug = ?
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid` from article
+where slug = ? and (status = 'published' or user_id = ?) and deleted_at is null
         "#,
-        slug
+        slug,
+        DbUuid(auth_user.user_id)
     )
         .fetch_optional(&mut tx)
         .await?
         .ok_or(Error::NotFound)?;
     
-    sqlx::query!(
+    let inserted = sqlx::query!(
         r#"
 insert ignore into article_favorite(article_id, user_id)
 values (?, ?)
         "#,
-        article_id,
-        auth_user.user_id.to_string()
+        article_meta.article_id,
+        DbUuid(auth_user.user_id)
     )
         .execute(&mut tx)
-        .await?;
+        .await?
+        .rows_affected();
+
+    adjust_article_counters(&mut tx, article_meta.article_id.0, inserted as i64, 0).await?;
+
+    let notified = if inserted > 0 {
+        notify(
+            &mut tx,
+            article_meta.user_id.0,
+            auth_user.user_id,
+            NotificationKind::Favorite,
+            Some(article_meta.article_id.0),
+            None,
+        )
+            .await?
+    } else {
+        None
+    };
 
-    let article_id = Uuid::from_str(&article_id).context("invalid uuid string")?;
-    let article = article_by_id(&mut tx, Some(auth_user.user_id), article_id).await?;
+    let article = article_by_id(
+        &mut tx,
+        Some(auth_user.user_id),
+        article_meta.article_id.0,
+        wants_body_html,
+    )
+        .await?;
 
     tx.commit().await?;
 
-    Ok(Json(ArticleBody { article }))
+    publish_notifications(&ctx, notified.as_slice()).await;
+
+    Ok(ArticleBody { article })
 }
 
 async fn unfavorite_article(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
-    Path(slug): Path<String>,
-) -> Result<Json<ArticleBody>> {
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
     let mut tx = ctx.db.begin().await?;
 
-    let article_id = sqlx::query_scalar!(
+    let article_id = DbUuid(visible_article_id(&mut tx, &slug, Some(auth_user.user_id)).await?);
+    
+    let deleted = sqlx::query!(
         r#"
-select article_id from article where slug = ?
+delete from article_favorite where article_id = ? and user_id = ?
+        "#,
+        article_id,
+        DbUuid(auth_user.user_id)
+    )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+    adjust_article_counters(&mut tx, article_id.0, -(deleted as i64), 0).await?;
+
+    let article =
+        article_by_id(&mut tx, Some(auth_user.user_id), article_id.0, wants_body_html).await?;
+
+    tx.commit().await?;
+
+    Ok(ArticleBody { article })
+}
+
+async fn publish_article(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
+    set_article_status(auth_user, ctx, wants_body_html, slug, ArticleStatus::Published).await
+}
+
+async fn unpublish_article(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
+    set_article_status(auth_user, ctx, wants_body_html, slug, ArticleStatus::Draft).await
+}
+
+async fn archive_article(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
+    set_article_status(auth_user, ctx, wants_body_html, slug, ArticleStatus::Archived).await
+}
+
+/// `published_at` records the first publication and is kept when an article is unpublished
+/// and published again. Any change of status cancels a pending scheduled publication.
+async fn set_article_status(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    slug: String,
+    status: ArticleStatus,
+) -> Result<ArticleBody> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_meta = sqlx::query!(
+        r#"
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid`, status `status: ArticleStatus`
+from article where slug = ? and deleted_at is null for update
         "#,
         slug
     )
         .fetch_optional(&mut tx)
         .await?
         .ok_or(Error::NotFound)?;
-    
+
+    if article_meta.user_id.0 != auth_user.user_id {
+        return Err(Error::Forbidden);
+    }
+
     sqlx::query!(
         r#"
-delete from article_favorite where article_id = ? and user_id = ?
+update article
+set
+    status = ?,
+    published_at = if(? = 'published', coalesce(published_at, current_timestamp), published_at),
+    publish_at = null,
+    version = version + 1
+where article_id = ?
         "#,
-        article_id,
-        auth_user.user_id.to_string()
+        status,
+        status,
+        article_meta.article_id
     )
         .execute(&mut tx)
         .await?;
 
-    let article_id = Uuid::from_str(&article_id).context("invalid uuid string")?;
-    let article = article_by_id(&mut tx, Some(auth_user.user_id), article_id).await?;
+    if status != article_meta.status {
+        // Receivers see an article appear the moment it becomes public.
+        let event = if status == ArticleStatus::Published {
+            WebhookEvent::ArticleCreated
+        } else {
+            WebhookEvent::ArticleUpdated
+        };
+
+        enqueue_article_event(
+            &mut tx,
+            event,
+            article_meta.article_id.0,
+            auth_user.user_id,
+        )
+            .await?;
+    }
+
+    let article = article_by_id(
+        &mut tx,
+        Some(auth_user.user_id),
+        article_meta.article_id.0,
+        wants_body_html,
+    )
+        .await?;
 
     tx.commit().await?;
 
-    Ok(Json(ArticleBody { article }))
+    if status == ArticleStatus::Published && article_meta.status != ArticleStatus::Published {
+        publish_new_article(&ctx.db, &ctx.events, article_meta.article_id.0, auth_user.user_id).await;
+    }
+
+    Ok(ArticleBody { article })
+}
+
+/// Streams a newly published article to the followers of its author, as seen by someone
+/// who hasn't interacted with it yet.
+async fn publish_new_article(db: &MySqlPool, events: &EventBus, article_id: Uuid, author_id: Uuid) {
+    match article_by_id(db, None, article_id, WantsBodyHtml(false)).await {
+        Ok(article) => events.publish(
+            Topic::Author(author_id),
+            "article",
+            &ArticleBody { article },
+        ),
+        Err(e) => log::error!("failed to load article {} to publish: {:?}", article_id, e),
+    }
+}
+
+/// Records an `article.*` webhook event with the article as someone who hasn't interacted
+/// with it sees it, rendered body included.
+async fn enqueue_article_event(
+    tx: &mut Transaction<'_, MySql>,
+    event: WebhookEvent,
+    article_id: Uuid,
+    author_id: Uuid,
+) -> Result<()> {
+    let article = article_by_id(&mut *tx, None, article_id, WantsBodyHtml(true)).await?;
+
+    webhooks::enqueue(tx, event, author_id, &ArticleBody { article }).await
 }
 
-#[allow(unused_variables)]
 async fn get_tags(
     ctx: Extension<ApiContext>,
 ) -> Result<Json<TagsBody>> {
-    todo!("not easy to implement on mysql using json data type")
+    let tags = sqlx::query_scalar!(
+        r#"
+select distinct tags.tag `tag!`
+from article,
+json_table(article.tag_list, '$[*]' columns (tag varchar(250) path '$')) tags
+where article.status = 'published'
+and article.deleted_at is null
+order by tags.tag
+        "#
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Json(TagsBody { tags }))
 }
 
+/// The ID of the article at `slug` if `user_id` may see it, i.e. it's published or their own.
+async fn visible_article_id(
+    e: impl Executor<'_, Database = MySql>,
+    slug: &str,
+    user_id: Option<Uuid>,
+) -> Result<Uuid> {
+    let article_id = sqlx::query_scalar!(
+        r#"
+select article_id `article_id: DbUuid` from article
+where slug = ? and (status = 'published' or user_id = ?) and deleted_at is null
+        "#,
+        slug,
+        user_id.map(DbUuid)
+    )
+        .fetch_optional(e)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(article_id.0)
+}
+
+/// Loads an article as `user_id` sees it, with the rendered body only if `wants_body_html`.
 async fn article_by_id(
     e: impl Executor<'_, Database = MySql>,
     user_id: Option<Uuid>,
     article_id: Uuid,
+    wants_body_html: WantsBodyHtml,
 ) -> Result<Article> {
     let article = sqlx::query_as!(
         ArticleFromQuery,
         r#"
 select
+    article.article_id `article_id: DbUuid`,
+    article.version,
     article.slug,
     article.title,
     article.description,
     article.body,
+    article.body_html,
     article.tag_list,
+    article.status `status: ArticleStatus`,
+    article.published_at `published_at: Timestamptz`,
+    article.publish_at `publish_at: Timestamptz`,
     article.created_at `created_at: Timestamptz`,
     article.updated_at `updated_at: Timestamptz`,
-    exists(select 1 from article_favorite where user_id = ?) `favorited!:_`,
-    coalesce(
-        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
-        0
-    ) `favorites_count!`,
+    exists(
+        select 1 from article_favorite
+        where article_favorite.article_id = article.article_id and article_favorite.user_id = ?
+    ) `favorited!:_`,
+    article.favorites_count,
+    article.comments_count,
+    article.comments_locked `comments_locked: bool`,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from article_reaction ar
+            where ar.article_id = article.article_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from article_reaction ar
+        where ar.article_id = article.article_id and ar.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from article_mention am
+        inner join user mentioned on mentioned.user_id = am.user_id
+        where am.article_id = article.article_id
+    ) `mentions: serde_json::Value`,
     user.username author_username,
     user.bio author_bio,
     user.image author_image,
-    0 `following_author:_`
+    0 `following_author:_`,
+    0 `sort_key!: i64`
 from article
 inner join user using (user_id)
 where article.article_id = ?
+and article.deleted_at is null
         "#,
-        user_id.map(|id| id.to_string()),
-        article_id.to_string()
+        user_id.map(DbUuid),
+        user_id.map(DbUuid),
+        DbUuid(article_id)
     )
         .fetch_optional(e)
         .await?
         .ok_or(Error::NotFound)?
-        .into_article();
+        .into_article()
+        .with_body_html(wants_body_html);
     
     Ok(article)
 }
\ No newline at end of file
@@ -0,0 +1,326 @@
+use axum::{Router, extract::{Extension, Path}, Json, routing::{get, post}};
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::{Timestamptz, DbBool, DbUuid}, extractor::{AuthUser, WantsBodyHtml}, ApiContext, Result, Error};
+
+use super::{article_by_id, ArticleBody, ArticleSlug};
+use super::comments::{comment_by_id, CommentBody};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/api/articles/:slug/lock",
+            post(lock_comments).delete(unlock_comments),
+        )
+        .route(
+            "/api/articles/:slug/comments/:comment_id/pin",
+            post(pin_comment).delete(unpin_comment),
+        )
+        .route("/api/articles/:slug/moderation", get(get_moderation_log))
+}
+
+/// What an article's author did to the discussion under it.
+#[derive(sqlx::Type, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "camelCase")]
+pub(super) enum ModerationAction {
+    LockComments,
+    UnlockComments,
+    PinComment,
+    UnpinComment,
+    /// The author removed a comment someone else wrote.
+    DeleteComment,
+}
+
+#[derive(serde::Serialize)]
+struct ModerationLogBody {
+    entries: Vec<ModerationEntry>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModerationEntry {
+    action: ModerationAction,
+    comment_id: Option<i64>,
+    created_at: Timestamptz,
+}
+
+async fn lock_comments(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
+    set_comments_locked(auth_user, ctx, wants_body_html, slug, true).await
+}
+
+async fn unlock_comments(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<ArticleBody> {
+    set_comments_locked(auth_user, ctx, wants_body_html, slug, false).await
+}
+
+async fn set_comments_locked(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    slug: String,
+    locked: bool,
+) -> Result<ArticleBody> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = lock_own_article(&mut tx, &slug, auth_user.user_id).await?;
+
+    let result = sqlx::query!(
+        r#"
+update article set comments_locked = ?, version = version + 1, updated_at = updated_at
+where article_id = ? and comments_locked != ?
+        "#,
+        locked,
+        DbUuid(article_id),
+        locked
+    )
+        .execute(&mut tx)
+        .await?;
+
+    if result.rows_affected() > 0 {
+        let action = if locked {
+            ModerationAction::LockComments
+        } else {
+            ModerationAction::UnlockComments
+        };
+
+        record_moderation(&mut tx, article_id, auth_user.user_id, action, None).await?;
+    }
+
+    let article =
+        article_by_id(&mut tx, Some(auth_user.user_id), article_id, wants_body_html).await?;
+
+    tx.commit().await?;
+
+    Ok(ArticleBody { article })
+}
+
+/// Pins a top-level comment above the other threads, up to `max_pinned_comments` per article.
+async fn pin_comment(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id)): Path<(String, i64)>,
+) -> Result<Json<CommentBody>> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = lock_own_article(&mut tx, &slug, auth_user.user_id).await?;
+
+    let comment = sqlx::query!(
+        r#"
+select parent_comment_id, pinned_at is not null `pinned!: DbBool`
+from article_comment
+where comment_id = ? and article_id = ? and deleted_at is null
+        "#,
+        comment_id,
+        DbUuid(article_id)
+    )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if comment.parent_comment_id.is_some() {
+        return Err(Error::unprocessable_entity([(
+            "comment",
+            "only top-level comments can be pinned",
+        )]));
+    }
+
+    if !bool::from(comment.pinned) {
+        // The article row is locked, so concurrent pins can't both get under the limit.
+        let pinned_count = sqlx::query_scalar!(
+            r#"
+select count(*) from article_comment
+where article_id = ? and pinned_at is not null and deleted_at is null
+            "#,
+            DbUuid(article_id)
+        )
+            .fetch_one(&mut tx)
+            .await?;
+
+        if pinned_count >= ctx.config.max_pinned_comments {
+            return Err(Error::unprocessable_entity([(
+                "comment",
+                format!(
+                    "at most {} comments can be pinned",
+                    ctx.config.max_pinned_comments
+                ),
+            )]));
+        }
+
+        sqlx::query!(
+            r#"
+update article_comment set pinned_at = current_timestamp, updated_at = updated_at
+where comment_id = ?
+            "#,
+            comment_id
+        )
+            .execute(&mut tx)
+            .await?;
+
+        record_moderation(
+            &mut tx,
+            article_id,
+            auth_user.user_id,
+            ModerationAction::PinComment,
+            Some(comment_id),
+        )
+            .await?;
+    }
+
+    let comment = comment_by_id(&mut tx, comment_id, auth_user.user_id)
+        .await?
+        .into_comment(Vec::new());
+
+    tx.commit().await?;
+
+    Ok(Json(CommentBody { comment }))
+}
+
+async fn unpin_comment(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id)): Path<(String, i64)>,
+) -> Result<Json<CommentBody>> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = lock_own_article(&mut tx, &slug, auth_user.user_id).await?;
+
+    sqlx::query_scalar!(
+        r#"
+select comment_id from article_comment
+where comment_id = ? and article_id = ? and deleted_at is null
+        "#,
+        comment_id,
+        DbUuid(article_id)
+    )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let result = sqlx::query!(
+        r#"
+update article_comment set pinned_at = null, updated_at = updated_at
+where comment_id = ? and pinned_at is not null
+        "#,
+        comment_id
+    )
+        .execute(&mut tx)
+        .await?;
+
+    if result.rows_affected() > 0 {
+        record_moderation(
+            &mut tx,
+            article_id,
+            auth_user.user_id,
+            ModerationAction::UnpinComment,
+            Some(comment_id),
+        )
+            .await?;
+    }
+
+    let comment = comment_by_id(&mut tx, comment_id, auth_user.user_id)
+        .await?
+        .into_comment(Vec::new());
+
+    tx.commit().await?;
+
+    Ok(Json(CommentBody { comment }))
+}
+
+/// The moderation history of an article, newest first. Only its author can read it.
+async fn get_moderation_log(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<Json<ModerationLogBody>> {
+    let article = sqlx::query!(
+        r#"
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid`
+from article where slug = ? and deleted_at is null
+        "#,
+        slug
+    )
+        .fetch_optional(&ctx.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if article.user_id.0 != auth_user.user_id {
+        return Err(Error::Forbidden);
+    }
+
+    let entries = sqlx::query_as!(
+        ModerationEntry,
+        r#"
+select
+    action `action: ModerationAction`,
+    comment_id,
+    created_at `created_at: Timestamptz`
+from moderation_log
+where article_id = ?
+order by entry_id desc
+        "#,
+        article.article_id
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Json(ModerationLogBody { entries }))
+}
+
+/// Locks the article at `slug` for the rest of the transaction, provided `user_id` wrote it.
+async fn lock_own_article(
+    tx: &mut Transaction<'_, MySql>,
+    slug: &str,
+    user_id: Uuid,
+) -> Result<Uuid> {
+    let article = sqlx::query!(
+        r#"
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid`
+from article where slug = ? and deleted_at is null for update
+        "#,
+        slug
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if article.user_id.0 != user_id {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(article.article_id.0)
+}
+
+pub(super) async fn record_moderation(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    user_id: Uuid,
+    action: ModerationAction,
+    comment_id: Option<i64>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+insert into moderation_log (article_id, user_id, action, comment_id) values (?, ?, ?, ?)
+        "#,
+        DbUuid(article_id),
+        DbUuid(user_id),
+        action,
+        comment_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use axum::{Router, extract::{Extension, Path}, Json, routing::put};
+use itertools::Itertools;
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::DbUuid, extractor::{AuthUser, WantsBodyHtml}, events::Topic, ApiContext, Result, Error};
+
+use super::{article_by_id, visible_article_id, ArticleBody, ArticleSlug};
+use super::comments::{comment_by_id, CommentBody};
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/api/articles/:slug/reactions/:reaction",
+            put(react_to_article).delete(unreact_to_article),
+        )
+        .route(
+            "/api/articles/:slug/comments/:comment_id/reactions/:reaction",
+            put(react_to_comment).delete(unreact_to_comment),
+        )
+}
+
+#[derive(serde::Serialize)]
+pub(super) struct Reaction {
+    reaction: String,
+    count: i64,
+    /// Whether the viewer left this reaction.
+    reacted: bool,
+}
+
+/// Turns the `reaction_counts` object and `own_reactions` array selected next to an article or
+/// comment into one entry per reaction, most used first.
+pub(super) fn summarize_reactions(
+    counts: Option<serde_json::Value>,
+    own: Option<serde_json::Value>,
+) -> Vec<Reaction> {
+    let counts: HashMap<String, i64> = counts
+        .and_then(|counts| serde_json::from_value(counts).ok())
+        .unwrap_or_default();
+    let own: Vec<String> = own
+        .and_then(|own| serde_json::from_value(own).ok())
+        .unwrap_or_default();
+
+    counts
+        .into_iter()
+        .map(|(reaction, count)| Reaction {
+            reacted: own.contains(&reaction),
+            reaction,
+            count,
+        })
+        .sorted_by(|a, b| b.count.cmp(&a.count).then_with(|| a.reaction.cmp(&b.reaction)))
+        .collect()
+}
+
+/// Sent to readers of an article's comments when a reaction on it or a comment changes.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReactionEvent<'a> {
+    article_slug: &'a str,
+    /// `None` for reactions on the article itself.
+    comment_id: Option<i64>,
+    reaction: &'a str,
+    /// How many users left the reaction now.
+    count: i64,
+    added: bool,
+}
+
+fn reaction_count(reactions: &[Reaction], reaction: &str) -> i64 {
+    reactions
+        .iter()
+        .find(|summary| summary.reaction == reaction)
+        .map_or(0, |summary| summary.count)
+}
+
+fn publish_reaction(
+    ctx: &ApiContext,
+    article_id: Uuid,
+    article_slug: &str,
+    comment_id: Option<i64>,
+    reaction: &str,
+    reactions: &[Reaction],
+    added: bool,
+) {
+    ctx.events.publish(
+        Topic::ArticleComments(article_id),
+        "reaction",
+        &ReactionEvent {
+            article_slug,
+            comment_id,
+            reaction,
+            count: reaction_count(reactions, reaction),
+            added,
+        },
+    );
+}
+
+fn validate_reaction(ctx: &ApiContext, reaction: &str) -> Result<()> {
+    if ctx.config.reactions.iter().any(|allowed| allowed == reaction) {
+        return Ok(());
+    }
+
+    Err(Error::unprocessable_entity([(
+        "reaction",
+        format!("must be one of {}", ctx.config.reactions.join(" ")),
+    )]))
+}
+
+async fn react_to_article(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, reaction)): Path<(String, String)>,
+) -> Result<ArticleBody> {
+    validate_reaction(&ctx, &reaction)?;
+
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = visible_article_id(&mut tx, &slug, Some(auth_user.user_id)).await?;
+
+    let result = sqlx::query!(
+        r#"
+insert ignore into article_reaction (article_id, user_id, reaction) values (?, ?, ?)
+        "#,
+        DbUuid(article_id),
+        DbUuid(auth_user.user_id),
+        reaction
+    )
+        .execute(&mut tx)
+        .await?;
+
+    let article =
+        article_by_id(&mut tx, Some(auth_user.user_id), article_id, wants_body_html).await?;
+
+    tx.commit().await?;
+
+    if result.rows_affected() > 0 {
+        publish_reaction(&ctx, article_id, &slug, None, &reaction, &article.reactions, true);
+    }
+
+    Ok(ArticleBody { article })
+}
+
+async fn unreact_to_article(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, reaction)): Path<(String, String)>,
+) -> Result<ArticleBody> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = visible_article_id(&mut tx, &slug, Some(auth_user.user_id)).await?;
+
+    let result = sqlx::query!(
+        r#"
+delete from article_reaction where article_id = ? and user_id = ? and reaction = ?
+        "#,
+        DbUuid(article_id),
+        DbUuid(auth_user.user_id),
+        reaction
+    )
+        .execute(&mut tx)
+        .await?;
+
+    let article =
+        article_by_id(&mut tx, Some(auth_user.user_id), article_id, wants_body_html).await?;
+
+    tx.commit().await?;
+
+    if result.rows_affected() > 0 {
+        publish_reaction(&ctx, article_id, &slug, None, &reaction, &article.reactions, false);
+    }
+
+    Ok(ArticleBody { article })
+}
+
+async fn react_to_comment(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id, reaction)): Path<(String, i64, String)>,
+) -> Result<Json<CommentBody>> {
+    validate_reaction(&ctx, &reaction)?;
+
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = check_comment_visible(&mut tx, &slug, comment_id, auth_user.user_id).await?;
+
+    let result = sqlx::query!(
+        r#"
+insert ignore into comment_reaction (comment_id, user_id, reaction) values (?, ?, ?)
+        "#,
+        comment_id,
+        DbUuid(auth_user.user_id),
+        reaction
+    )
+        .execute(&mut tx)
+        .await?;
+
+    let comment = comment_by_id(&mut tx, comment_id, auth_user.user_id)
+        .await?
+        .into_comment(Vec::new());
+
+    tx.commit().await?;
+
+    if result.rows_affected() > 0 {
+        publish_reaction(
+            &ctx,
+            article_id.0,
+            &slug,
+            Some(comment_id),
+            &reaction,
+            &comment.reactions,
+            true,
+        );
+    }
+
+    Ok(Json(CommentBody { comment }))
+}
+
+async fn unreact_to_comment(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, comment_id, reaction)): Path<(String, i64, String)>,
+) -> Result<Json<CommentBody>> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_id = check_comment_visible(&mut tx, &slug, comment_id, auth_user.user_id).await?;
+
+    let result = sqlx::query!(
+        r#"
+delete from comment_reaction where comment_id = ? and user_id = ? and reaction = ?
+        "#,
+        comment_id,
+        DbUuid(auth_user.user_id),
+        reaction
+    )
+        .execute(&mut tx)
+        .await?;
+
+    let comment = comment_by_id(&mut tx, comment_id, auth_user.user_id)
+        .await?
+        .into_comment(Vec::new());
+
+    tx.commit().await?;
+
+    if result.rows_affected() > 0 {
+        publish_reaction(
+            &ctx,
+            article_id.0,
+            &slug,
+            Some(comment_id),
+            &reaction,
+            &comment.reactions,
+            false,
+        );
+    }
+
+    Ok(Json(CommentBody { comment }))
+}
+
+/// The ID of the article the comment is under, if both are visible to `user_id`.
+async fn check_comment_visible(
+    tx: &mut Transaction<'_, MySql>,
+    slug: &str,
+    comment_id: i64,
+    user_id: Uuid,
+) -> Result<DbUuid> {
+    sqlx::query_scalar!(
+        r#"
+select comment.article_id `article_id: DbUuid`
+from article_comment comment
+inner join article on article.article_id = comment.article_id
+where comment.comment_id = ? and article.slug = ?
+and (article.status = 'published' or article.user_id = ?)
+and comment.deleted_at is null and article.deleted_at is null
+        "#,
+        comment_id,
+        slug,
+        DbUuid(user_id)
+    )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)
+}
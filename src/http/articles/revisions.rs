@@ -0,0 +1,335 @@
+use axum::{Router, extract::{Extension, Path, Query}, Json, routing::{get, post}};
+use similar::{ChangeTag, TextDiff};
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::{Timestamptz, DbUuid}, extractor::{AuthUser, MaybeAuthUser, WantsBodyHtml}, notifications::publish_notifications, webhooks::WebhookEvent, ApiContext, Result, Error};
+
+use super::{article_by_id, enqueue_article_event, visible_article_id, markdown, mentions, rename_article, retry_on_deadlock, unique_slug, Article, ArticleBody, ArticleSlug};
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/articles/:slug/revisions", get(list_revisions))
+        .route("/api/articles/:slug/revisions/:revision", get(get_revision))
+        .route(
+            "/api/articles/:slug/revisions/:revision/diff",
+            get(diff_revisions),
+        )
+        .route(
+            "/api/articles/:slug/revisions/:revision/restore",
+            post(restore_revision),
+        )
+}
+
+#[derive(serde::Serialize)]
+struct RevisionBody<T = Revision> {
+    revision: T,
+}
+
+#[derive(serde::Serialize)]
+struct MultipleRevisionsBody {
+    revisions: Vec<RevisionSummary>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RevisionSummary {
+    revision: i32,
+    title: String,
+    author: String,
+    created_at: Timestamptz,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Revision {
+    revision: i32,
+    title: String,
+    description: String,
+    body: String,
+    author: String,
+    created_at: Timestamptz,
+}
+
+#[derive(serde::Deserialize)]
+struct DiffQuery {
+    /// Revision to compare against; defaults to the one before. Revision 0 is the empty
+    /// article the first revision is compared against.
+    from: Option<i32>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RevisionDiff {
+    from: i32,
+    to: i32,
+    title: Vec<DiffLine>,
+    description: Vec<DiffLine>,
+    body: Vec<DiffLine>,
+}
+
+#[derive(serde::Serialize)]
+struct DiffLine {
+    op: DiffOp,
+    text: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// Snapshots the current content of an article as its next revision, unless it's unchanged
+/// since the latest one (e.g. only the tags were edited).
+///
+/// Callers must hold a `for update` lock on the article row so revision numbers can't race.
+pub(super) async fn record_revision(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    user_id: Uuid,
+) -> Result<()> {
+    // Compared as binary so a change of case or accents counts as an edit.
+    sqlx::query!(
+        r#"
+insert into article_revision (article_id, revision, user_id, title, description, body)
+select
+    article.article_id,
+    coalesce(latest.revision, 0) + 1,
+    ?,
+    article.title,
+    article.description,
+    article.body
+from article
+left join article_revision latest
+    on latest.article_id = article.article_id
+    and latest.revision = (select max(revision) from article_revision where article_id = ?)
+where article.article_id = ?
+and not (
+    latest.revision is not null
+    and cast(latest.title as binary) = cast(article.title as binary)
+    and cast(latest.description as binary) = cast(article.description as binary)
+    and cast(latest.body as binary) = cast(article.body as binary)
+)
+        "#,
+        DbUuid(user_id),
+        DbUuid(article_id),
+        DbUuid(article_id)
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+async fn fetch_revision(ctx: &ApiContext, article_id: Uuid, revision: i32) -> Result<Revision> {
+    let revision = sqlx::query_as!(
+        Revision,
+        r#"
+select
+    revision.revision,
+    revision.title,
+    revision.description,
+    revision.body,
+    user.username author,
+    revision.created_at `created_at: Timestamptz`
+from article_revision revision
+inner join user using (user_id)
+where revision.article_id = ? and revision.revision = ?
+        "#,
+        DbUuid(article_id),
+        revision
+    )
+        .fetch_optional(&ctx.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(revision)
+}
+
+async fn list_revisions(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+) -> Result<Json<MultipleRevisionsBody>> {
+    let article_id = visible_article_id(&ctx.db, &slug, maybe_auth_user.user_id()).await?;
+
+    let revisions = sqlx::query_as!(
+        RevisionSummary,
+        r#"
+select
+    revision.revision,
+    revision.title,
+    user.username author,
+    revision.created_at `created_at: Timestamptz`
+from article_revision revision
+inner join user using (user_id)
+where revision.article_id = ?
+order by revision.revision desc
+        "#,
+        DbUuid(article_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Json(MultipleRevisionsBody { revisions }))
+}
+
+async fn get_revision(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, revision)): Path<(String, i32)>,
+) -> Result<Json<RevisionBody>> {
+    let article_id = visible_article_id(&ctx.db, &slug, maybe_auth_user.user_id()).await?;
+
+    let revision = fetch_revision(&ctx, article_id, revision).await?;
+
+    Ok(Json(RevisionBody { revision }))
+}
+
+async fn diff_revisions(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, revision)): Path<(String, i32)>,
+    query: Query<DiffQuery>,
+) -> Result<Json<RevisionBody<RevisionDiff>>> {
+    let article_id = visible_article_id(&ctx.db, &slug, maybe_auth_user.user_id()).await?;
+
+    let from = query.from.unwrap_or(revision - 1);
+
+    let new = fetch_revision(&ctx, article_id, revision).await?;
+    let old = match from {
+        0 => None,
+        from => Some(fetch_revision(&ctx, article_id, from).await?),
+    };
+    let old = old.as_ref();
+
+    Ok(Json(RevisionBody {
+        revision: RevisionDiff {
+            from,
+            to: revision,
+            title: diff_lines(old.map_or("", |old| old.title.as_str()), &new.title),
+            description: diff_lines(old.map_or("", |old| old.description.as_str()), &new.description),
+            body: diff_lines(old.map_or("", |old| old.body.as_str()), &new.body),
+        },
+    }))
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            op: match change.tag() {
+                ChangeTag::Equal => DiffOp::Equal,
+                ChangeTag::Insert => DiffOp::Insert,
+                ChangeTag::Delete => DiffOp::Delete,
+            },
+            text: change.value().trim_end_matches('\n').to_string(),
+        })
+        .collect()
+}
+
+/// Copies an earlier revision back onto the article. The restored content is recorded as a
+/// new revision, so history is never rewritten.
+async fn restore_revision(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    ArticleSlug(slug): ArticleSlug,
+    Path((_, revision)): Path<(String, i32)>,
+) -> Result<ArticleBody> {
+    let (article, notified) = retry_on_deadlock(|| {
+        apply_revision(&ctx, auth_user.user_id, wants_body_html, &slug, revision)
+    })
+        .await?;
+
+    publish_notifications(&ctx, &notified).await;
+
+    Ok(ArticleBody { article })
+}
+
+/// The transaction of `restore_revision`. Returns the restored article and the notifications
+/// of newly mentioned users.
+async fn apply_revision(
+    ctx: &ApiContext,
+    user_id: Uuid,
+    wants_body_html: WantsBodyHtml,
+    slug: &str,
+    revision: i32,
+) -> Result<(Article, Vec<i64>)> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_meta = sqlx::query!(
+        r#"
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid`
+from article where slug = ? and deleted_at is null for update
+        "#,
+        slug
+    )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if article_meta.user_id.0 != user_id {
+        return Err(Error::Forbidden);
+    }
+
+    let restored = sqlx::query!(
+        r#"
+select title, description, body from article_revision where article_id = ? and revision = ?
+        "#,
+        article_meta.article_id,
+        revision
+    )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let new_slug =
+        unique_slug(&mut tx, &restored.title, article_meta.article_id.0, Some(slug)).await?;
+    rename_article(&mut tx, article_meta.article_id.0, &restored.title, slug, new_slug).await?;
+
+    sqlx::query!(
+        r#"
+update article
+set
+    title = ?,
+    description = ?,
+    body = ?,
+    body_html = ?,
+    version = version + 1
+where article_id = ?
+        "#,
+        restored.title,
+        restored.description,
+        restored.body,
+        markdown::render(&restored.body),
+        article_meta.article_id
+    )
+        .execute(&mut tx)
+        .await?;
+
+    record_revision(&mut tx, article_meta.article_id.0, user_id).await?;
+    let notified = mentions::record_article_mentions(
+        &mut tx,
+        article_meta.article_id.0,
+        user_id,
+        &restored.body,
+    )
+        .await?;
+
+    enqueue_article_event(&mut tx, WebhookEvent::ArticleUpdated, article_meta.article_id.0, user_id)
+        .await?;
+
+    let article =
+        article_by_id(&mut tx, Some(user_id), article_meta.article_id.0, wants_body_html).await?;
+
+    
+    tx.commit().await?;
+
+    Ok((article, notified))
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use sqlx::MySqlPool;
+use tokio::task::JoinHandle;
+
+use crate::http::{events::EventBus, types::DbUuid, webhooks::WebhookEvent, Result};
+
+use super::{enqueue_article_event, publish_new_article};
+
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Maximum number of articles published per transaction.
+const PUBLISH_BATCH_SIZE: i64 = 100;
+
+/// Periodically publishes drafts whose `publish_at` has passed.
+///
+/// Due rows are claimed with `for update skip locked`, so several server instances can run
+/// the scheduler against the same database without publishing an article twice or blocking
+/// each other. Published articles are streamed to the followers of their authors.
+pub(in crate::http) fn spawn_publish_scheduler(db: MySqlPool, events: EventBus) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match publish_due_articles(&db, &events).await {
+                    Ok(published) => {
+                        if published > 0 {
+                            log::info!("published {} scheduled articles", published);
+                        }
+
+                        if published < PUBLISH_BATCH_SIZE as usize {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("failed to publish scheduled articles: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn publish_due_articles(db: &MySqlPool, events: &EventBus) -> Result<usize> {
+    let mut tx = db.begin().await?;
+
+    let articles = sqlx::query!(
+        r#"
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid` from article
+where status = 'draft' and publish_at <= current_timestamp and deleted_at is null
+order by publish_at
+limit ?
+for update skip locked
+        "#,
+        PUBLISH_BATCH_SIZE
+    )
+        .fetch_all(&mut tx)
+        .await?;
+
+    for article in &articles {
+        sqlx::query!(
+            r#"
+update article
+set
+    status = 'published',
+    published_at = coalesce(published_at, publish_at),
+    publish_at = null,
+    version = version + 1
+where article_id = ?
+            "#,
+            article.article_id
+        )
+            .execute(&mut tx)
+            .await?;
+
+        enqueue_article_event(
+            &mut tx,
+            WebhookEvent::ArticleCreated,
+            article.article_id.0,
+            article.user_id.0,
+        )
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    for article in &articles {
+        publish_new_article(db, events, article.article_id.0, article.user_id.0).await;
+    }
+
+    Ok(articles.len())
+}
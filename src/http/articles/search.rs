@@ -0,0 +1,339 @@
+use axum::{extract::{Extension, Query}, Json};
+use uuid::Uuid;
+
+use crate::http::{extractor::{MaybeAuthUser, WantsBodyHtml}, ApiContext, Error, types::{Timestamptz, DbUuid}};
+
+use super::{Article, ArticleFromQuery, ArticleStatus, Result};
+use super::listing::{author_ids, split_list, MatchMode, DEFAULT_LIMIT, MAX_LIMIT};
+
+/// Characters of context kept on either side of the first match in a snippet.
+const SNIPPET_CONTEXT: usize = 60;
+
+const SNIPPET_LENGTH: usize = 200;
+
+#[derive(serde::Deserialize)]
+pub struct SearchArticlesQuery {
+    q: String,
+    #[serde(default)]
+    mode: SearchMode,
+    tag: Option<String>,
+    #[serde(rename = "tagMode")]
+    tag_mode: Option<MatchMode>,
+    author: Option<String>,
+    favorited: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Maps onto MySQL's `IN NATURAL LANGUAGE MODE` and `IN BOOLEAN MODE` search modifiers.
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    Natural,
+    Boolean,
+}
+
+impl Default for SearchMode {
+    fn default() -> Self {
+        Self::Natural
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResultsBody {
+    articles: Vec<SearchResult>,
+    articles_count: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    article: Article,
+    /// Excerpt around the first match, HTML-escaped, with matched terms wrapped in `<mark>`.
+    snippet: String,
+}
+
+pub(in crate::http) async fn search_articles(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    query: Query<SearchArticlesQuery>,
+) -> Result<Json<SearchResultsBody>> {
+    let q = query.q.trim();
+
+    if q.is_empty() {
+        return Err(Error::unprocessable_entity([("q", "can't be blank")]));
+    }
+
+    let boolean_mode = query.mode == SearchMode::Boolean;
+    let tags = split_list(query.tag.as_deref());
+    // Handed to MySQL as a JSON array of UUIDs, matched against `article.user_id` below.
+    let author_ids = author_ids(&ctx, query.author.as_deref())
+        .await?
+        .map(|author_ids| {
+            serde_json::Value::from(author_ids.iter().map(Uuid::to_string).collect::<Vec<_>>())
+        });
+    let match_all_tags = query.tag_mode == Some(MatchMode::All);
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let articles_count = sqlx::query_scalar!(
+        r#"
+select count(*)
+from article
+where article.status = 'published'
+and article.deleted_at is null
+and if(
+    ?,
+    match(article.title, article.description, article.body) against (? in boolean mode),
+    match(article.title, article.description, article.body) against (? in natural language mode)
+) > 0
+and (
+    ? is null or article.user_id in (
+        select uuid_to_bin(authors.user_id)
+        from JSON_TABLE(?, '$[*]' columns (user_id char(36) path '$')) authors
+    )
+) and (
+    ? is null or if(?, JSON_CONTAINS(article.tag_list, ?), JSON_OVERLAPS(article.tag_list, ?))
+) and (
+    ? is null or exists(
+        select 1 from user
+        inner join article_favorite af using (user_id)
+        where user.username = ?
+        and af.article_id = article.article_id
+    )
+)
+        "#,
+        boolean_mode,
+        q,
+        q,
+        author_ids,
+        author_ids,
+        tags,
+        match_all_tags,
+        tags,
+        tags,
+        query.favorited,
+        query.favorited,
+    )
+        .fetch_one(&ctx.db)
+        .await?;
+
+    let rows = sqlx::query_as!(
+        ArticleFromQuery,
+        r#"
+select
+    article.article_id `article_id: DbUuid`,
+    article.version,
+    slug,
+    title,
+    description,
+    body,
+    body_html,
+    tag_list,
+    article.status `status: ArticleStatus`,
+    article.published_at `published_at: Timestamptz`,
+    article.publish_at `publish_at: Timestamptz`,
+    article.created_at `created_at: Timestamptz`,
+    article.updated_at `updated_at: Timestamptz`,
+    exists(
+        select 1 from article_favorite
+        where article_favorite.article_id = article.article_id and article_favorite.user_id = ?
+    ) `favorited!:_`,
+    article.favorites_count,
+    article.comments_count,
+    article.comments_locked `comments_locked: bool`,
+    (
+        select json_objectagg(counts.reaction, counts.n)
+        from (
+            select reaction, count(*) n from article_reaction ar
+            where ar.article_id = article.article_id
+            group by reaction
+        ) counts
+    ) `reaction_counts: serde_json::Value`,
+    (
+        select json_arrayagg(reaction) from article_reaction ar
+        where ar.article_id = article.article_id and ar.user_id = ?
+    ) `own_reactions: serde_json::Value`,
+    (
+        select json_arrayagg(mentioned.username)
+        from article_mention am
+        inner join user mentioned on mentioned.user_id = am.user_id
+        where am.article_id = article.article_id
+    ) `mentions: serde_json::Value`,
+    author.username author_username,
+    author.bio author_bio,
+    author.image author_image,
+    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = ?) `following_author!:_`,
+    0 `sort_key!: i64`
+from article
+inner join user author using (user_id)
+where article.status = 'published'
+and article.deleted_at is null
+and if(
+    ?,
+    match(article.title, article.description, article.body) against (? in boolean mode),
+    match(article.title, article.description, article.body) against (? in natural language mode)
+) > 0
+and (
+    ? is null or article.user_id in (
+        select uuid_to_bin(authors.user_id)
+        from JSON_TABLE(?, '$[*]' columns (user_id char(36) path '$')) authors
+    )
+) and (
+    ? is null or if(?, JSON_CONTAINS(article.tag_list, ?), JSON_OVERLAPS(article.tag_list, ?))
+) and (
+    ? is null or exists(
+        select 1 from user
+        inner join article_favorite af using (user_id)
+        where user.username = ?
+        and af.article_id = article.article_id
+    )
+)
+order by
+    if(
+        ?,
+        match(article.title, article.description, article.body) against (? in boolean mode),
+        match(article.title, article.description, article.body) against (? in natural language mode)
+    ) desc,
+    article.created_at desc
+limit ?
+offset ?
+        "#,
+        maybe_auth_user.user_id().map(DbUuid),
+        maybe_auth_user.user_id().map(DbUuid),
+        maybe_auth_user.user_id().map(DbUuid),
+        boolean_mode,
+        q,
+        q,
+        author_ids,
+        author_ids,
+        tags,
+        match_all_tags,
+        tags,
+        tags,
+        query.favorited,
+        query.favorited,
+        boolean_mode,
+        q,
+        q,
+        limit,
+        offset
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    let terms = search_terms(q);
+
+    let articles = rows
+        .into_iter()
+        .map(|row| {
+            let snippet = snippet(&row.description, &row.body, &terms);
+            SearchResult {
+                article: row.into_article().with_body_html(wants_body_html),
+                snippet,
+            }
+        })
+        .collect();
+
+    Ok(Json(SearchResultsBody {
+        articles,
+        articles_count,
+    }))
+}
+
+/// Extracts the words to highlight from a search query, dropping boolean mode operators.
+fn search_terms(q: &str) -> Vec<Vec<char>> {
+    const OPERATORS: &[char] = &['+', '-', '<', '>', '~', '*', '"', '(', ')', '@'];
+
+    let mut terms: Vec<Vec<char>> = q
+        .split(|c: char| c.is_whitespace() || OPERATORS.contains(&c))
+        .filter(|term| !term.is_empty())
+        .map(|term| term.chars().map(lowercase).collect())
+        .collect();
+
+    // Prefer the longest match when terms overlap.
+    terms.sort_by(|a, b| b.len().cmp(&a.len()));
+    terms.dedup();
+    terms
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Picks an excerpt of the body (or the description, if only that matched) around the first
+/// search term and highlights every term inside it.
+fn snippet(description: &str, body: &str, terms: &[Vec<char>]) -> String {
+    let body: Vec<char> = body.chars().collect();
+    let description: Vec<char> = description.chars().collect();
+
+    let (text, first_match) = match find_term(&body, terms, 0) {
+        Some((pos, _)) => (&body, pos),
+        None => match find_term(&description, terms, 0) {
+            Some((pos, _)) => (&description, pos),
+            None => (&description, 0),
+        },
+    };
+
+    let start = first_match.saturating_sub(SNIPPET_CONTEXT);
+    let end = text.len().min(start + SNIPPET_LENGTH);
+
+    let mut snippet = String::new();
+
+    if start > 0 {
+        snippet.push('…');
+    }
+
+    let mut pos = start;
+    while pos < end {
+        match find_term(&text[..end], terms, pos) {
+            Some((found, len)) => {
+                push_escaped(&mut snippet, &text[pos..found]);
+                snippet.push_str("<mark>");
+                push_escaped(&mut snippet, &text[found..found + len]);
+                snippet.push_str("</mark>");
+                pos = found + len;
+            }
+            None => {
+                push_escaped(&mut snippet, &text[pos..end]);
+                pos = end;
+            }
+        }
+    }
+
+    if end < text.len() {
+        snippet.push('…');
+    }
+
+    snippet
+}
+
+/// Returns the position and length of the first term occurring in `text` at or after `from`.
+fn find_term(text: &[char], terms: &[Vec<char>], from: usize) -> Option<(usize, usize)> {
+    (from..text.len()).find_map(|pos| {
+        terms
+            .iter()
+            .find(|term| {
+                text.len() - pos >= term.len()
+                    && text[pos..pos + term.len()]
+                        .iter()
+                        .zip(term.iter())
+                        .all(|(&c, &t)| lowercase(c) == t)
+            })
+            .map(|term| (pos, term.len()))
+    })
+}
+
+fn push_escaped(out: &mut String, text: &[char]) {
+    for &c in text {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use axum::{
+    body::{boxed, Body, BoxBody},
+    extract::{Extension, FromRequest, Path, RequestParts},
+    http::{header, HeaderMap, HeaderValue, Method, Response, StatusCode},
+    response::IntoResponse,
+};
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::DbUuid, ApiContext, Error, Result};
+
+/// The `:slug` of an article route, resolved through `slug_history`.
+///
+/// Slugs an article had before a title change keep working: `GET` requests are answered
+/// with a `301` to the same path under the current slug, other methods act on the article
+/// transparently.
+pub(super) struct ArticleSlug(pub String);
+
+#[async_trait]
+impl FromRequest for ArticleSlug {
+    type Rejection = Response<BoxBody>;
+
+    async fn from_request(req: &mut RequestParts<Body>) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<HashMap<String, String>>::from_request(req)
+            .await
+            .map_err(|e| e.into_response().map(boxed))?;
+
+        let slug = params
+            .get("slug")
+            .cloned()
+            .ok_or_else(|| Error::NotFound.into_response().map(boxed))?;
+
+        let ctx: Extension<ApiContext> = Extension::from_request(req)
+            .await
+            .expect("ApiContext was not added as an extension");
+
+        let current_slug = sqlx::query_scalar!(
+            r#"
+select article.slug
+from slug_history
+inner join article using (article_id)
+where slug_history.slug = ?
+and article.deleted_at is null
+and not exists(select 1 from article live where live.slug = ?)
+            "#,
+            slug,
+            slug
+        )
+            .fetch_optional(&ctx.db)
+            .await
+            .map_err(|e| Error::from(e).into_response().map(boxed))?;
+
+        let current_slug = match current_slug {
+            Some(current_slug) => current_slug,
+            None => return Ok(Self(slug)),
+        };
+
+        if req.method() != Method::GET {
+            return Ok(Self(current_slug));
+        }
+
+        // Paths look like `/api/articles/:slug/...`.
+        let path = req
+            .uri()
+            .path()
+            .split('/')
+            .enumerate()
+            .map(|(i, segment)| if i == 3 { current_slug.as_str() } else { segment })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let location = match req.uri().query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::LOCATION,
+            HeaderValue::from_str(&location).map_err(|_| Error::NotFound.into_response().map(boxed))?,
+        );
+
+        Err((StatusCode::MOVED_PERMANENTLY, headers).into_response().map(boxed))
+    }
+}
+
+/// Remembers `old_slug` so links to it keep resolving after the article was renamed.
+pub(super) async fn record_slug_change(
+    tx: &mut Transaction<'_, MySql>,
+    article_id: Uuid,
+    old_slug: &str,
+    new_slug: &str,
+) -> Result<()> {
+    if old_slug == new_slug {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+insert into slug_history (slug, article_id) values (?, ?)
+on duplicate key update article_id = values(article_id), created_at = current_timestamp
+        "#,
+        old_slug,
+        DbUuid(article_id)
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    // The article went back to one of its old slugs, which is live again now.
+    sqlx::query!(
+        r#"
+delete from slug_history where slug = ?
+        "#,
+        new_slug
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
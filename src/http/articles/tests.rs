@@ -0,0 +1,83 @@
+use uuid::Uuid;
+
+use super::{
+    fallback_slug, first_free_slug, short_id, slug_candidates, slugify, MAX_SLUG_LENGTH,
+    MAX_SLUG_SUFFIX,
+};
+
+fn article_id() -> Uuid {
+    Uuid::parse_str("0184e8a1-3c2d-7f00-8a1b-2c3d4e5f6a7b").unwrap()
+}
+
+#[test]
+fn slugify_transliterates_unicode() {
+    assert_eq!(slugify("日本語"), "ri-ben-yu");
+    assert_eq!(slugify("Crème brûlée à la française"), "creme-brulee-a-la-francaise");
+    assert_eq!(slugify("Straße in Köln"), "strasse-in-koln");
+    assert_eq!(slugify("Rust 2024: What's New?"), "rust-2024-whats-new");
+}
+
+#[test]
+fn punctuation_only_titles_fall_back_to_the_short_id() {
+    for title in ["?!", "--- ... ---", "", "   "] {
+        assert_eq!(slugify(title), "", "{:?}", title);
+        assert_eq!(fallback_slug(title, article_id()), "4e5f6a7b", "{:?}", title);
+    }
+
+    assert_eq!(short_id(article_id()), "4e5f6a7b");
+}
+
+#[test]
+fn long_slugs_are_cut_at_a_word_boundary() {
+    let slug = slugify(&"Ünïcödé wörds ".repeat(40));
+    let full = "unicode-words-".repeat(40);
+
+    assert!(full.len() > MAX_SLUG_LENGTH);
+    assert!(slug.len() <= MAX_SLUG_LENGTH, "{}", slug);
+    assert!(full.starts_with(&slug));
+    assert!(full[slug.len()..].starts_with('-'), "{}", slug);
+}
+
+#[test]
+fn long_single_words_are_cut_at_the_maximum_length() {
+    let slug = slugify(&"日".repeat(MAX_SLUG_LENGTH));
+    assert!(slug.len() <= MAX_SLUG_LENGTH);
+    assert!(!slug.ends_with('-'), "{}", slug);
+
+    assert_eq!(slugify(&"é".repeat(MAX_SLUG_LENGTH * 2)), "e".repeat(MAX_SLUG_LENGTH));
+}
+
+#[test]
+fn colliding_slugs_get_a_numeric_suffix() {
+    let candidates = slug_candidates("hello-world", article_id());
+
+    assert_eq!(
+        first_free_slug(candidates.clone(), &[]),
+        Some("hello-world".to_string())
+    );
+    assert_eq!(
+        first_free_slug(
+            candidates.clone(),
+            &["hello-world".to_string(), "hello-world-2".to_string()]
+        ),
+        Some("hello-world-3".to_string())
+    );
+    // A free slug earlier in the order is reused.
+    assert_eq!(
+        first_free_slug(candidates, &["hello-world-2".to_string()]),
+        Some("hello-world".to_string())
+    );
+}
+
+#[test]
+fn exhausted_suffixes_fall_back_to_the_short_id() {
+    let candidates = slug_candidates("hello-world", article_id());
+    let taken: Vec<String> = candidates[..MAX_SLUG_SUFFIX].to_vec();
+
+    assert_eq!(
+        first_free_slug(candidates.clone(), &taken),
+        Some("hello-world-4e5f6a7b".to_string())
+    );
+    assert_eq!(candidates.last(), Some(&fallback_slug("Hello, world!", article_id())));
+    assert_eq!(first_free_slug(candidates.clone(), &candidates), None);
+}
@@ -0,0 +1,333 @@
+use std::time::Duration;
+
+use axum::{Router, extract::{Extension, Path}, Json, routing::{get, post}};
+use sqlx::MySqlPool;
+use tokio::task::JoinHandle;
+
+use crate::http::{types::{Timestamptz, DbBool, DbUuid}, extractor::{AuthUser, WantsBodyHtml}, webhooks::WebhookEvent, ApiContext, Result, Error};
+
+use super::{adjust_article_counters, article_by_id, enqueue_article_event, ArticleBody};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of articles or comments purged per transaction.
+const PURGE_BATCH_SIZE: i64 = 100;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/user/trash", get(list_trash))
+        .route(
+            "/api/user/trash/articles/:slug/restore",
+            post(restore_article),
+        )
+        .route(
+            "/api/user/trash/comments/:comment_id/restore",
+            post(restore_comment),
+        )
+}
+
+#[derive(serde::Serialize)]
+struct TrashBody {
+    articles: Vec<TrashedArticle>,
+    comments: Vec<TrashedComment>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrashedArticle {
+    slug: String,
+    title: String,
+    deleted_at: Timestamptz,
+    purge_at: Timestamptz,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrashedComment {
+    id: i64,
+    body: String,
+    article_slug: String,
+    deleted_at: Timestamptz,
+    purge_at: Timestamptz,
+}
+
+async fn list_trash(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+) -> Result<Json<TrashBody>> {
+    let retention = time::Duration::days(ctx.config.trash_retention_days);
+
+    let articles = sqlx::query!(
+        r#"
+select slug, title, deleted_at `deleted_at!: Timestamptz`
+from article
+where user_id = ? and deleted_at is not null
+order by deleted_at desc
+        "#,
+        DbUuid(auth_user.user_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(|row| TrashedArticle {
+            slug: row.slug,
+            title: row.title,
+            purge_at: Timestamptz(row.deleted_at.0 + retention),
+            deleted_at: row.deleted_at,
+        })
+        .collect();
+
+    let comments = sqlx::query!(
+        r#"
+select
+    comment.comment_id,
+    comment.body,
+    article.slug article_slug,
+    comment.deleted_at `deleted_at!: Timestamptz`
+from article_comment comment
+inner join article using (article_id)
+where comment.user_id = ? and comment.deleted_at is not null
+-- Comments removed by the article's author can't be brought back by whoever wrote them.
+and not exists(
+    select 1 from moderation_log log
+    where log.comment_id = comment.comment_id and log.action = 'delete_comment'
+)
+order by comment.deleted_at desc
+        "#,
+        DbUuid(auth_user.user_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(|row| TrashedComment {
+            id: row.comment_id,
+            body: row.body,
+            article_slug: row.article_slug,
+            purge_at: Timestamptz(row.deleted_at.0 + retention),
+            deleted_at: row.deleted_at,
+        })
+        .collect();
+
+    Ok(Json(TrashBody { articles, comments }))
+}
+
+async fn restore_article(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    wants_body_html: WantsBodyHtml,
+    Path(slug): Path<String>,
+) -> Result<ArticleBody> {
+    let mut tx = ctx.db.begin().await?;
+
+    let article_meta = sqlx::query!(
+        r#"
+select article_id `article_id: DbUuid`, user_id `user_id: DbUuid`
+from article where slug = ? and deleted_at is not null for update
+        "#,
+        slug
+    )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if article_meta.user_id.0 != auth_user.user_id {
+        return Err(Error::Forbidden);
+    }
+
+    sqlx::query!(
+        r#"
+update article set deleted_at = null, version = version + 1 where article_id = ?
+        "#,
+        article_meta.article_id
+    )
+        .execute(&mut tx)
+        .await?;
+
+    // Receivers that dropped the article on `article.deleted` pick it up again from here.
+    enqueue_article_event(
+        &mut tx,
+        WebhookEvent::ArticleUpdated,
+        article_meta.article_id.0,
+        auth_user.user_id,
+    )
+        .await?;
+
+    let article = article_by_id(
+        &mut tx,
+        Some(auth_user.user_id),
+        article_meta.article_id.0,
+        wants_body_html,
+    )
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(ArticleBody { article })
+}
+
+async fn restore_comment(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Path(comment_id): Path<i64>,
+) -> Result<()> {
+    let mut tx = ctx.db.begin().await?;
+
+    let comment = sqlx::query!(
+        r#"
+select
+    comment.user_id `user_id: DbUuid`,
+    comment.article_id `article_id: DbUuid`,
+    article.deleted_at is not null `article_deleted!: DbBool`
+from article_comment comment
+inner join article using (article_id)
+where comment.comment_id = ? and comment.deleted_at is not null
+and not exists(
+    select 1 from moderation_log log
+    where log.comment_id = comment.comment_id and log.action = 'delete_comment'
+)
+for update
+        "#,
+        comment_id
+    )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if comment.user_id.0 != auth_user.user_id {
+        return Err(Error::Forbidden);
+    }
+
+    if bool::from(comment.article_deleted) {
+        return Err(Error::unprocessable_entity([(
+            "comment",
+            "restore the article it was written under first",
+        )]));
+    }
+
+    sqlx::query!(
+        r#"
+update article_comment set deleted_at = null, updated_at = updated_at where comment_id = ?
+        "#,
+        comment_id
+    )
+        .execute(&mut tx)
+        .await?;
+
+    adjust_article_counters(&mut tx, comment.article_id.0, 0, 1).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Periodically removes articles and comments that have been in the trash for longer than
+/// `retention_days`.
+///
+/// Like the publish scheduler, rows are claimed with `for update skip locked` so several
+/// instances can purge concurrently.
+pub(in crate::http) fn spawn_trash_purger(db: MySqlPool, retention_days: i64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match purge_expired_articles(&db, retention_days).await {
+                    Ok(purged) => {
+                        if purged > 0 {
+                            log::info!("purged {} deleted articles", purged);
+                        }
+
+                        if purged < PURGE_BATCH_SIZE as usize {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("failed to purge deleted articles: {:?}", e);
+                        break;
+                    }
+                }
+            }
+
+            loop {
+                match purge_expired_comments(&db, retention_days).await {
+                    Ok(purged) => {
+                        if purged > 0 {
+                            log::info!("purged {} deleted comments", purged);
+                        }
+
+                        if purged < PURGE_BATCH_SIZE as usize {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("failed to purge deleted comments: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn purge_expired_articles(db: &MySqlPool, retention_days: i64) -> Result<usize> {
+    let mut tx = db.begin().await?;
+
+    let article_ids = sqlx::query_scalar!(
+        r#"
+select article_id from article
+where deleted_at < current_timestamp - interval ? day
+order by deleted_at
+limit ?
+for update skip locked
+        "#,
+        retention_days,
+        PURGE_BATCH_SIZE
+    )
+        .fetch_all(&mut tx)
+        .await?;
+
+    // Comments, favorites, revisions and old slugs go with the article via `on delete cascade`.
+    for article_id in &article_ids {
+        sqlx::query!("delete from article where article_id = ?", article_id)
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(article_ids.len())
+}
+
+async fn purge_expired_comments(db: &MySqlPool, retention_days: i64) -> Result<usize> {
+    let mut tx = db.begin().await?;
+
+    let comment_ids = sqlx::query_scalar!(
+        r#"
+select comment_id from article_comment comment
+where deleted_at < current_timestamp - interval ? day
+-- Tombstones stay until their replies are gone.
+and not exists(
+    select 1 from article_comment reply where reply.parent_comment_id = comment.comment_id
+)
+order by deleted_at
+limit ?
+for update skip locked
+        "#,
+        retention_days,
+        PURGE_BATCH_SIZE
+    )
+        .fetch_all(&mut tx)
+        .await?;
+
+    for comment_id in &comment_ids {
+        sqlx::query!("delete from article_comment where comment_id = ?", comment_id)
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(comment_ids.len())
+}
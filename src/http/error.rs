@@ -9,6 +9,15 @@ use sqlx::error::DatabaseError;
 use sqlx::mysql::MySqlDatabaseError;
 use std::{borrow::Cow, collections::HashMap};
 
+/// `ER_ROW_IS_REFERENCED_2`: a parent row can't be deleted or updated while children refer to it.
+const ER_ROW_IS_REFERENCED: u16 = 1451;
+
+/// `ER_NO_REFERENCED_ROW_2`: a child row refers to a parent row that doesn't exist.
+const ER_NO_REFERENCED_ROW: u16 = 1452;
+
+/// `ER_LOCK_DEADLOCK`: the transaction was rolled back to break a deadlock.
+const ER_LOCK_DEADLOCK: u16 = 1213;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     /// Return `401 Unauthorized`
@@ -23,6 +32,18 @@ pub enum Error {
     #[error("request path not found")]
     NotFound,
 
+    /// Return `409 Conflict`
+    #[error("the resource is still referenced by other resources")]
+    Conflict,
+
+    /// Return `412 Precondition Failed`
+    #[error("the resource has been modified")]
+    PreconditionFailed,
+
+    /// Return `428 Precondition Required`
+    #[error("this resource requires an If-Match header")]
+    PreconditionRequired,
+
     /// Return `422 Unprocessable Entity`
     #[error("error in the request body")]
     UnprocessableEntity {
@@ -56,11 +77,27 @@ impl Error {
         Self::UnprocessableEntity { errors: error_map }
     }
 
+    /// Returns `true` if MySQL rolled the whole transaction back to break a deadlock, so it
+    /// can be retried from the start.
+    pub fn is_deadlock(&self) -> bool {
+        match self {
+            Self::Sqlx(sqlx::Error::Database(dbe)) => {
+                dbe.try_downcast_ref::<MySqlDatabaseError>()
+                    .map(MySqlDatabaseError::number)
+                    == Some(ER_LOCK_DEADLOCK)
+            }
+            _ => false,
+        }
+    }
+
     fn status_code(&self) -> StatusCode {
         match self {
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Self::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
             Self::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
             Self::Sqlx(_) | Self::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -92,7 +129,18 @@ impl IntoResponse for Error {
                     .into_response();
             }
             Self::Sqlx(ref e) => {
-                log::error!("SQLx error: {:?}", e);
+                let violation = match e {
+                    sqlx::Error::Database(dbe) => foreign_key_violation(dbe.try_downcast_ref()),
+                    _ => None,
+                };
+
+                // Foreign key violations that weren't mapped with `on_constraint` still mean the
+                // request referred to something missing, or tried to remove something in use.
+                match violation {
+                    Some(ER_ROW_IS_REFERENCED) => return Self::Conflict.into_response(),
+                    Some(_) => return Self::NotFound.into_response(),
+                    None => log::error!("SQLx error: {:?}", e),
+                }
             }
             Self::Anyhow(ref e) => {
                 log::error!("Generic error: {:?}", e);
@@ -105,6 +153,7 @@ impl IntoResponse for Error {
 }
 
 pub trait ResultExt<T> {
+    /// Maps a violation of the unique key or foreign key constraint `name` with `f`.
     fn on_constraint(
         self,
         name: &str,
@@ -124,7 +173,12 @@ where
         self.map_err(|e| match e.into() {
             Error::Sqlx(sqlx::Error::Database(dbe)) if {
                 let mbe = dbe.downcast_ref::<MySqlDatabaseError>();
-                if mbe.code() == Some("23000") {
+                if foreign_key_violation(Some(mbe)).is_some() {
+                    let reg = Regex::new(r"CONSTRAINT `(\w+)`").expect("invalid regex");
+                    let foreign_key = reg.captures(mbe.message())
+                        .and_then(|cap| cap.get(1).map(|str| str.as_str()));
+                    foreign_key == Some(name)
+                } else if mbe.code() == Some("23000") {
                     let reg = Regex::new(r"Duplicate .+'(\w+)'").expect("invalid regext");
                     let duplicate_key = reg.captures(mbe.message())
                         .and_then(|cap| {
@@ -143,3 +197,9 @@ where
         })
     }
 }
+
+/// Returns the error number if `e` is a foreign key violation.
+fn foreign_key_violation(e: Option<&MySqlDatabaseError>) -> Option<u16> {
+    e.map(MySqlDatabaseError::number)
+        .filter(|&number| number == ER_ROW_IS_REFERENCED || number == ER_NO_REFERENCED_ROW)
+}
@@ -0,0 +1,269 @@
+//! In-process bus carrying the live updates streamed to clients.
+//!
+//! Handlers publish after their transaction commits. Every event gets an ID made of the
+//! process start time and a sequence number; the last `RECENT_EVENTS` are kept around so a
+//! client reconnecting with `Last-Event-ID` gets what it missed. When that is no longer
+//! possible (the server restarted, or the client fell too far behind) the stream starts with
+//! a `reset` event, telling the client to refetch instead.
+//!
+//! Transient events, like typing indicators, are broadcast but neither numbered nor kept.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use super::{types::DbUuid, ApiContext, Result};
+
+use axum::Router;
+
+mod sse;
+mod ws;
+
+pub fn router() -> Router {
+    sse::router().merge(ws::router())
+}
+
+/// Events kept for clients resuming with `Last-Event-ID`.
+const RECENT_EVENTS: usize = 1024;
+
+/// Events buffered per subscriber before it starts missing them.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Who an event is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    /// Readers of the comments of an article.
+    ArticleComments(Uuid),
+    /// Followers of an author.
+    Author(Uuid),
+    /// A single user.
+    User(Uuid),
+}
+
+#[derive(Debug)]
+pub struct Event {
+    /// `0` for transient events.
+    pub id: u64,
+    pub topic: Topic,
+    /// The SSE event name, e.g. `comment`.
+    pub name: &'static str,
+    pub data: serde_json::Value,
+    /// Who caused a transient event, so it isn't echoed back to them.
+    pub actor: Option<Uuid>,
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Start of this process in milliseconds, telling apart IDs handed out before a restart.
+    boot: i128,
+    sender: broadcast::Sender<Arc<Event>>,
+    recent: Mutex<Recent>,
+}
+
+struct Recent {
+    last_id: u64,
+    events: VecDeque<Arc<Event>>,
+}
+
+/// The events a client receives: its own, and those about what it watches.
+#[derive(Debug)]
+pub struct Subscription {
+    pub user_id: Uuid,
+    pub article_ids: HashSet<Uuid>,
+    pub followed_user_ids: HashSet<Uuid>,
+}
+
+/// A subscriber's starting point.
+pub struct Resumed {
+    /// Events published since the `Last-Event-ID` the client sent.
+    pub replay: Vec<Arc<Event>>,
+    /// Whether events may have been lost in between, so the client must refetch.
+    pub reset: bool,
+    pub receiver: broadcast::Receiver<Arc<Event>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+
+        Self {
+            inner: Arc::new(Inner {
+                boot: OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000,
+                sender,
+                recent: Mutex::new(Recent {
+                    last_id: 0,
+                    events: VecDeque::with_capacity(RECENT_EVENTS),
+                }),
+            }),
+        }
+    }
+
+    pub fn publish(&self, topic: Topic, name: &'static str, data: &impl serde::Serialize) {
+        let data = match serialize_event(name, data) {
+            Some(data) => data,
+            None => return,
+        };
+
+        let mut recent = self.inner.recent.lock().expect("event bus lock poisoned");
+
+        recent.last_id += 1;
+
+        let event = Arc::new(Event {
+            id: recent.last_id,
+            topic,
+            name,
+            data,
+            actor: None,
+        });
+
+        if recent.events.len() == RECENT_EVENTS {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+
+        // Sending under the lock keeps the broadcast in the same order as `recent`.
+        // There being no subscribers is not an error.
+        let _ = self.inner.sender.send(event);
+    }
+
+    /// Broadcasts an event to current subscribers only; it can't be replayed.
+    pub fn publish_transient(
+        &self,
+        topic: Topic,
+        name: &'static str,
+        data: &impl serde::Serialize,
+        actor: Uuid,
+    ) {
+        let data = match serialize_event(name, data) {
+            Some(data) => data,
+            None => return,
+        };
+
+        let _ = self.inner.sender.send(Arc::new(Event {
+            id: 0,
+            topic,
+            name,
+            data,
+            actor: Some(actor),
+        }));
+    }
+
+    /// Subscribes to new events, picking up after `last_event_id` if the client sent one.
+    pub fn subscribe(&self, last_event_id: Option<&str>) -> Resumed {
+        let recent = self.inner.recent.lock().expect("event bus lock poisoned");
+
+        // Subscribing under the lock means nothing slips between the replay and the receiver.
+        let receiver = self.inner.sender.subscribe();
+
+        let after = match last_event_id {
+            Some(last_event_id) => self.parse_event_id(last_event_id),
+            None => {
+                return Resumed {
+                    replay: Vec::new(),
+                    reset: false,
+                    receiver,
+                }
+            }
+        };
+
+        let oldest = recent.events.front().map_or(recent.last_id + 1, |event| event.id);
+
+        match after {
+            Some(after) if after + 1 >= oldest && after <= recent.last_id => Resumed {
+                replay: recent
+                    .events
+                    .iter()
+                    .filter(|event| event.id > after)
+                    .cloned()
+                    .collect(),
+                reset: false,
+                receiver,
+            },
+            _ => Resumed {
+                replay: Vec::new(),
+                reset: true,
+                receiver,
+            },
+        }
+    }
+
+    pub fn event_id(&self, event: &Event) -> String {
+        format!("{}-{}", self.inner.boot, event.id)
+    }
+
+    /// The sequence number in an ID handed out by this process.
+    fn parse_event_id(&self, event_id: &str) -> Option<u64> {
+        let (boot, id) = event_id.split_once('-')?;
+
+        if boot.parse::<i128>().ok()? != self.inner.boot {
+            return None;
+        }
+
+        id.parse().ok()
+    }
+}
+
+fn serialize_event(name: &str, data: &impl serde::Serialize) -> Option<serde_json::Value> {
+    serde_json::to_value(data)
+        .map_err(|e| log::error!("failed to serialize {} event: {}", name, e))
+        .ok()
+}
+
+/// What `user_id` receives when watching the comments of the articles at `slugs`.
+///
+/// Followed authors are looked up once, so following someone takes effect on the next
+/// connection.
+async fn subscription(ctx: &ApiContext, user_id: Uuid, slugs: &[&str]) -> Result<Subscription> {
+    let mut article_ids = HashSet::new();
+
+    // One lookup per slug uses the unique index on it, which matching all of them in a single
+    // statement wouldn't.
+    for &slug in slugs {
+        let article_id = sqlx::query_scalar!(
+            r#"
+select article_id `article_id: DbUuid` from article
+where slug = ?
+and (status = 'published' or user_id = ?)
+and deleted_at is null
+            "#,
+            slug,
+            DbUuid(user_id)
+        )
+            .fetch_optional(&ctx.db)
+            .await?;
+
+        article_ids.extend(article_id.map(|article_id| article_id.0));
+    }
+
+    let followed_user_ids = sqlx::query_scalar!(
+        r#"
+select followed_user_id `followed_user_id: DbUuid` from follow where following_user_id = ?
+        "#,
+        DbUuid(user_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Subscription {
+        user_id,
+        article_ids,
+        followed_user_ids: followed_user_ids.into_iter().map(|user_id| user_id.0).collect(),
+    })
+}
+
+impl Subscription {
+    pub fn wants(&self, event: &Event) -> bool {
+        match event.topic {
+            Topic::ArticleComments(article_id) => self.article_ids.contains(&article_id),
+            Topic::Author(user_id) => self.followed_user_ids.contains(&user_id),
+            Topic::User(user_id) => self.user_id == user_id,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Extension, Query},
+    http::HeaderMap,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::http::{extractor::AuthUser, ApiContext, Result};
+
+use super::{Event, EventBus, Subscription};
+
+pub fn router() -> Router {
+    Router::new().route("/api/events", get(stream_events))
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct StreamEventsQuery {
+    /// Comma-separated slugs of the articles to receive new comments for.
+    articles: Option<String>,
+}
+
+struct StreamState {
+    bus: EventBus,
+    subscription: Subscription,
+    reset: bool,
+    replay: VecDeque<Arc<Event>>,
+    receiver: broadcast::Receiver<Arc<Event>>,
+}
+
+/// Streams new comments and reactions on the articles in `?articles=`, new articles from followed authors
+/// and new notifications of the current user.
+async fn stream_events(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    headers: HeaderMap,
+    Query(query): Query<StreamEventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>> {
+    let slugs: Vec<&str> = query
+        .articles
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|slug| !slug.is_empty())
+        .collect();
+
+    let subscription = super::subscription(&ctx, auth_user.user_id, &slugs).await?;
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|last_event_id| last_event_id.to_str().ok());
+
+    let resumed = ctx.events.subscribe(last_event_id);
+
+    let state = StreamState {
+        bus: ctx.events.clone(),
+        subscription,
+        reset: resumed.reset,
+        replay: resumed.replay.into(),
+        receiver: resumed.receiver,
+    };
+
+    let stream = stream::unfold(state, |mut state| async move {
+        loop {
+            if state.reset {
+                state.reset = false;
+                return Some((Ok(SseEvent::default().event("reset").data("{}")), state));
+            }
+
+            let event = match state.replay.pop_front() {
+                Some(event) => event,
+                None => match state.receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => {
+                        state.reset = true;
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+
+            // Typing indicators and the like are only sent over WebSockets.
+            if event.id == 0 || !state.subscription.wants(&event) {
+                continue;
+            }
+
+            let sse_event = SseEvent::default()
+                .id(state.bus.event_id(&event))
+                .event(event.name)
+                .data(event.data.to_string());
+
+            return Some((Ok(sse_event), state));
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
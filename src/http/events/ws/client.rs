@@ -0,0 +1,78 @@
+//! A WebSocket client talking to the API over an in-memory pipe, for tests that shouldn't
+//! need a port to listen on.
+
+use anyhow::Context;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use hyper::server::conn::Http;
+use tokio::io::DuplexStream;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    WebSocketStream,
+};
+
+/// Bytes buffered in each direction of the pipe.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+pub struct WsTestClient {
+    socket: WebSocketStream<DuplexStream>,
+    server: JoinHandle<()>,
+}
+
+impl WsTestClient {
+    /// Opens `/api/ws` on `app` as the user `token` was issued to.
+    pub async fn connect(app: Router, token: &str) -> anyhow::Result<Self> {
+        let (client_io, server_io) = tokio::io::duplex(PIPE_CAPACITY);
+
+        let server = tokio::spawn(async move {
+            if let Err(e) = Http::new()
+                .http1_only(true)
+                .serve_connection(server_io, app)
+                .with_upgrades()
+                .await
+            {
+                log::debug!("in-memory connection failed: {}", e);
+            }
+        });
+
+        // The host is never resolved, the request goes straight into the pipe.
+        let mut request = "ws://localhost/api/ws".into_client_request()?;
+        request.headers_mut().insert(
+            "authorization",
+            HeaderValue::from_str(&format!("Token {}", token))?,
+        );
+
+        let (socket, _) = tokio_tungstenite::client_async(request, client_io)
+            .await
+            .context("WebSocket handshake failed")?;
+
+        Ok(Self { socket, server })
+    }
+
+    pub async fn send(&mut self, message: &serde_json::Value) -> anyhow::Result<()> {
+        self.socket.send(Message::Text(message.to_string())).await?;
+        Ok(())
+    }
+
+    /// The next JSON message from the server, or `None` once it closed the connection.
+    ///
+    /// Pings are answered while waiting.
+    pub async fn recv(&mut self) -> anyhow::Result<Option<serde_json::Value>> {
+        while let Some(message) = self.socket.next().await {
+            match message? {
+                Message::Text(text) => return Ok(Some(serde_json::from_str(&text)?)),
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub async fn close(mut self) -> anyhow::Result<()> {
+        self.socket.close(None).await?;
+        self.server.await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,338 @@
+//! `/api/ws`, a bidirectional alternative to the SSE stream.
+//!
+//! Clients authenticate while upgrading, with the usual `Authorization` header or, since
+//! browsers can't set headers on WebSockets, a `?token=` parameter. After that every message is
+//! a JSON object with a `type`:
+//!
+//! * `{"type": "subscribe", "articles": ["slug"]}` starts sending the comment and reaction events
+//!   of those articles, `unsubscribe` stops it. Both are answered with `subscribed`, listing
+//!   every article watched now; slugs that don't resolve to a visible article are left out.
+//! * `{"type": "typing", "article": "slug"}` tells the other readers of a watched article that
+//!   the user is writing a comment.
+//!
+//! The server sends `{"type": "comment" | "reaction" | "typing", "data": {...}}` with the same
+//! data as the SSE events, `error` when it couldn't handle a message, and `reset` when the
+//! client fell behind and missed events.
+//!
+//! The server pings every `ws_ping_interval_seconds` and hangs up on clients it hasn't heard
+//! from in `ws_idle_timeout_seconds` (see [`Config`][crate::config::Config]). Clients that don't read what is sent to them are dropped after
+//! `SEND_TIMEOUT`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension, Query,
+    },
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::http::{extractor::AuthUser, types::DbUuid, ApiContext, Error, Result};
+
+use super::{Event, Topic};
+
+#[cfg(test)]
+pub mod client;
+
+#[cfg(test)]
+mod tests;
+
+/// How long sending a message may take before the client is considered stuck.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client messages read ahead of the one being handled. Once full, the socket isn't read
+/// until the session catches up.
+const INCOMING_CAPACITY: usize = 16;
+
+/// Upper bound for the size of a client message.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Articles a single connection can watch.
+const MAX_SUBSCRIPTIONS: usize = 50;
+
+/// Typing indicators are forwarded at most this often per user and article.
+const TYPING_INTERVAL: Duration = Duration::from_secs(3);
+
+pub fn router() -> Router {
+    Router::new().route("/api/ws", get(upgrade))
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct UpgradeQuery {
+    token: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ClientMessage {
+    Subscribe { articles: Vec<String> },
+    Unsubscribe { articles: Vec<String> },
+    Typing { article: String },
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ServerMessage<'a> {
+    Subscribed { articles: Vec<&'a str> },
+    Error { message: String },
+    Reset,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TypingEvent<'a> {
+    article_slug: &'a str,
+    username: &'a str,
+}
+
+struct Session {
+    ctx: ApiContext,
+    user_id: Uuid,
+    username: String,
+    /// Slugs of the watched articles by ID.
+    articles: HashMap<Uuid, String>,
+    /// When the user was last announced as typing, by article ID.
+    typing: HashMap<Uuid, Instant>,
+}
+
+async fn upgrade(
+    ws: WebSocketUpgrade,
+    ctx: Extension<ApiContext>,
+    headers: HeaderMap,
+    Query(query): Query<UpgradeQuery>,
+) -> Result<impl IntoResponse> {
+    let auth_user = match (headers.get(header::AUTHORIZATION), &query.token) {
+        (Some(auth_header), _) => AuthUser::from_authorization(&ctx, auth_header)?,
+        (None, Some(token)) => AuthUser::from_token(&ctx, token)?,
+        (None, None) => return Err(Error::Unauthorized),
+    };
+
+    let username = sqlx::query_scalar!(
+        r#"select username from user where user_id = ?"#,
+        DbUuid(auth_user.user_id)
+    )
+        .fetch_optional(&ctx.db)
+        .await?
+        .ok_or(Error::Unauthorized)?;
+
+    let session = Session {
+        ctx: ctx.0,
+        user_id: auth_user.user_id,
+        username,
+        articles: HashMap::new(),
+        typing: HashMap::new(),
+    };
+
+    Ok(ws
+        .max_message_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| session.run(socket)))
+}
+
+impl Session {
+    async fn run(mut self, socket: WebSocket) {
+        let (mut sender, mut receiver) = socket.split();
+
+        // Reading on a separate task lets pongs and closes through while a message is being
+        // handled, and the bounded channel stops reading from clients that send too much.
+        let (incoming_tx, mut incoming) = mpsc::channel(INCOMING_CAPACITY);
+
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(message)) = receiver.next().await {
+                if incoming_tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut events = self.ctx.events.subscribe(None).receiver;
+        let idle_timeout = Duration::from_secs(self.ctx.config.ws_idle_timeout_seconds);
+        let mut heartbeat =
+            time::interval(Duration::from_secs(self.ctx.config.ws_ping_interval_seconds));
+        let mut last_seen = Instant::now();
+
+        loop {
+            let outgoing = tokio::select! {
+                message = incoming.recv() => {
+                    last_seen = Instant::now();
+
+                    match message {
+                        Some(Message::Text(text)) => match self.handle(&text).await {
+                            Some(reply) => reply,
+                            None => continue,
+                        },
+                        Some(Message::Close(_)) | None => break,
+                        Some(_) => continue,
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) => match self.forward(&event) {
+                        Some(message) => message,
+                        None => continue,
+                    },
+                    Err(RecvError::Lagged(_)) => to_message(&ServerMessage::Reset),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > idle_timeout {
+                        log::debug!("closing idle WebSocket of user {}", self.user_id);
+                        break;
+                    }
+
+                    Message::Ping(Vec::new())
+                }
+            };
+
+            match time::timeout(SEND_TIMEOUT, sender.send(outgoing)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    log::debug!("WebSocket of user {} failed: {}", self.user_id, e);
+                    break;
+                }
+                Err(_) => {
+                    log::debug!("dropping stuck WebSocket of user {}", self.user_id);
+                    break;
+                }
+            }
+        }
+
+        reader.abort();
+
+        let _ = time::timeout(SEND_TIMEOUT, sender.close()).await;
+    }
+
+    /// Handles a client message, returning the reply if there is one.
+    async fn handle(&mut self, text: &str) -> Option<Message> {
+        let message = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => return Some(error_message(format!("invalid message: {}", e))),
+        };
+
+        match message {
+            ClientMessage::Subscribe { articles } => {
+                // Counting the requested slugs rather than the articles found keeps the limit
+                // independent of which of them exist.
+                if self.articles.len() + articles.len() > MAX_SUBSCRIPTIONS {
+                    return Some(error_message(format!(
+                        "at most {} articles can be watched",
+                        MAX_SUBSCRIPTIONS
+                    )));
+                }
+
+                if let Err(e) = self.subscribe(&articles).await {
+                    log::error!("failed to subscribe to articles: {:?}", e);
+                    return Some(error_message(e.to_string()));
+                }
+
+                Some(self.subscribed())
+            }
+            ClientMessage::Unsubscribe { articles } => {
+                self.articles.retain(|_, slug| !articles.contains(slug));
+                self.typing.retain(|article_id, _| self.articles.contains_key(article_id));
+
+                Some(self.subscribed())
+            }
+            ClientMessage::Typing { article } => self.typing(&article),
+        }
+    }
+
+    async fn subscribe(&mut self, slugs: &[String]) -> Result<()> {
+        // One lookup per slug uses the unique index on it, which matching all of them in a
+        // single statement wouldn't.
+        for slug in slugs {
+            if self.articles.values().any(|watched| watched == slug) {
+                continue;
+            }
+
+            let article_id = sqlx::query_scalar!(
+                r#"
+select article_id `article_id: DbUuid` from article
+where slug = ?
+and (status = 'published' or user_id = ?)
+and deleted_at is null
+                "#,
+                slug,
+                DbUuid(self.user_id)
+            )
+                .fetch_optional(&self.ctx.db)
+                .await?;
+
+            if let Some(article_id) = article_id {
+                self.articles.insert(article_id.0, slug.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn subscribed(&self) -> Message {
+        let mut articles: Vec<&str> = self.articles.values().map(String::as_str).collect();
+        articles.sort_unstable();
+
+        to_message(&ServerMessage::Subscribed { articles })
+    }
+
+    fn typing(&mut self, slug: &str) -> Option<Message> {
+        let article_id = match self.articles.iter().find(|(_, watched)| *watched == slug) {
+            Some((article_id, _)) => *article_id,
+            None => return Some(error_message(format!("not subscribed to {}", slug))),
+        };
+
+        let now = Instant::now();
+
+        if let Some(last) = self.typing.get(&article_id) {
+            if now.duration_since(*last) < TYPING_INTERVAL {
+                return None;
+            }
+        }
+
+        self.typing.insert(article_id, now);
+
+        self.ctx.events.publish_transient(
+            Topic::ArticleComments(article_id),
+            "typing",
+            &TypingEvent {
+                article_slug: slug,
+                username: &self.username,
+            },
+            self.user_id,
+        );
+
+        None
+    }
+
+    /// The message for a bus event, if this client watches it.
+    fn forward(&self, event: &Arc<Event>) -> Option<Message> {
+        let watched = match event.topic {
+            Topic::ArticleComments(article_id) => self.articles.contains_key(&article_id),
+            Topic::Author(_) | Topic::User(_) => false,
+        };
+
+        if !watched || event.actor == Some(self.user_id) {
+            return None;
+        }
+
+        Some(to_message(&serde_json::json!({
+            "type": event.name,
+            "data": event.data,
+        })))
+    }
+}
+
+fn to_message(message: &impl serde::Serialize) -> Message {
+    Message::Text(serde_json::to_string(message).expect("messages serialize to JSON"))
+}
+
+fn error_message(message: String) -> Message {
+    to_message(&ServerMessage::Error { message })
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+use tokio::time;
+
+use crate::http::events::{Topic, SUBSCRIBER_CAPACITY};
+use crate::http::test_support::{TestApp, TestUser};
+
+use super::client::WsTestClient;
+
+/// How long to wait for a message that should arrive.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn connect(app: &TestApp, user: &TestUser) -> WsTestClient {
+    WsTestClient::connect(app.router.clone(), &user.token)
+        .await
+        .expect("WebSocket connects")
+}
+
+async fn recv(client: &mut WsTestClient) -> Value {
+    time::timeout(RECV_TIMEOUT, client.recv())
+        .await
+        .expect("a message arrives in time")
+        .expect("the message is JSON")
+        .expect("the connection is open")
+}
+
+/// Subscribes to `slugs`, returning every slug watched afterwards.
+async fn subscribe(client: &mut WsTestClient, slugs: &[&str]) -> Value {
+    client
+        .send(&json!({ "type": "subscribe", "articles": slugs }))
+        .await
+        .unwrap();
+
+    let reply = recv(client).await;
+    assert_eq!(reply["type"], "subscribed", "{}", reply);
+
+    reply["articles"].clone()
+}
+
+/// Returns the messages sent before the replies to two no-op subscriptions.
+///
+/// The session picks between ready client messages and events at random, so an event already
+/// published may come after the first reply. It can't come after the second: the session
+/// forwards it while the test waits for the first.
+async fn drain(client: &mut WsTestClient) -> Vec<Value> {
+    let mut before = Vec::new();
+
+    for _ in 0..2 {
+        client
+            .send(&json!({ "type": "subscribe", "articles": [] }))
+            .await
+            .unwrap();
+
+        loop {
+            let message = recv(client).await;
+
+            if message["type"] == "subscribed" {
+                break;
+            }
+
+            before.push(message);
+        }
+    }
+
+    before
+}
+
+#[tokio::test]
+async fn subscribe_and_unsubscribe() {
+    let app = TestApp::new(&[]).await.unwrap();
+    let author = app.register_user().await.unwrap();
+    let reader = app.register_user().await.unwrap();
+    let slug = app.create_article(&author).await.unwrap();
+
+    let mut client = connect(&app, &reader).await;
+
+    assert_eq!(
+        subscribe(&mut client, &[slug.as_str(), "no-such-article"]).await,
+        json!([slug])
+    );
+
+    client
+        .send(&json!({ "type": "unsubscribe", "articles": [slug] }))
+        .await
+        .unwrap();
+
+    let reply = recv(&mut client).await;
+    assert_eq!(reply, json!({ "type": "subscribed", "articles": [] }));
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/articles/{}/comments", slug),
+            Some(&author.token),
+            Some(json!({ "comment": { "body": "Anyone there?" } })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(drain(&mut client).await, Vec::<Value>::new());
+
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn comments_and_reactions_fan_out() {
+    let app = TestApp::new(&["--reactions", "+1,heart"]).await.unwrap();
+    let author = app.register_user().await.unwrap();
+    let reader = app.register_user().await.unwrap();
+    let slug = app.create_article(&author).await.unwrap();
+
+    let mut first = connect(&app, &reader).await;
+    let mut second = connect(&app, &author).await;
+    subscribe(&mut first, &[slug.as_str()]).await;
+    subscribe(&mut second, &[slug.as_str()]).await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/api/articles/{}/comments", slug),
+            Some(&reader.token),
+            Some(json!({ "comment": { "body": "First!" } })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    for client in [&mut first, &mut second] {
+        let message = recv(client).await;
+        assert_eq!(message["type"], "comment", "{}", message);
+        assert_eq!(message["data"]["comment"]["body"], "First!", "{}", message);
+    }
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &format!("/api/articles/{}/reactions/heart", slug),
+            Some(&reader.token),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    for client in [&mut first, &mut second] {
+        let message = recv(client).await;
+        assert_eq!(message["type"], "reaction", "{}", message);
+        assert_eq!(message["data"]["articleSlug"], slug.as_str(), "{}", message);
+        assert_eq!(message["data"]["reaction"], "heart", "{}", message);
+        assert_eq!(message["data"]["count"], 1, "{}", message);
+    }
+
+    first.close().await.unwrap();
+    second.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn typing_is_throttled_and_not_echoed() {
+    let app = TestApp::new(&[]).await.unwrap();
+    let author = app.register_user().await.unwrap();
+    let reader = app.register_user().await.unwrap();
+    let slug = app.create_article(&author).await.unwrap();
+
+    let mut typist = connect(&app, &reader).await;
+    let mut watcher = connect(&app, &author).await;
+    subscribe(&mut typist, &[slug.as_str()]).await;
+    subscribe(&mut watcher, &[slug.as_str()]).await;
+
+    for _ in 0..3 {
+        typist
+            .send(&json!({ "type": "typing", "article": slug }))
+            .await
+            .unwrap();
+    }
+
+    // Messages are handled in order, so by now every typing message was, too.
+    assert_eq!(drain(&mut typist).await, Vec::<Value>::new());
+
+    assert_eq!(
+        recv(&mut watcher).await,
+        json!({
+            "type": "typing",
+            "data": { "articleSlug": slug, "username": reader.username },
+        })
+    );
+    assert_eq!(drain(&mut watcher).await, Vec::<Value>::new());
+
+    typist.close().await.unwrap();
+    watcher.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn idle_clients_are_disconnected() {
+    let app = TestApp::new(&["--ws-ping-interval-seconds", "1", "--ws-idle-timeout-seconds", "2"])
+        .await
+        .unwrap();
+    let user = app.register_user().await.unwrap();
+
+    let mut client = connect(&app, &user).await;
+
+    // Not reading means not answering pings either.
+    time::sleep(Duration::from_secs(4)).await;
+
+    let closed = time::timeout(RECV_TIMEOUT, client.recv())
+        .await
+        .expect("the server hangs up in time");
+
+    // Answering the pings read on the way to the close frame may fail as well, since the
+    // server no longer listens.
+    assert!(!matches!(closed, Ok(Some(_))), "{:?}", closed);
+}
+
+#[tokio::test]
+async fn lagging_clients_are_reset() {
+    let app = TestApp::new(&[]).await.unwrap();
+    let author = app.register_user().await.unwrap();
+    let slug = app.create_article(&author).await.unwrap();
+    let article_id = app.article_id(&slug).await.unwrap();
+
+    let mut client = connect(&app, &author).await;
+    subscribe(&mut client, &[slug.as_str()]).await;
+
+    // The test runtime has a single thread, so the session can't keep up with this.
+    for n in 0..SUBSCRIBER_CAPACITY + 10 {
+        app.events.publish(
+            Topic::ArticleComments(article_id),
+            "comment",
+            &json!({ "n": n }),
+        );
+    }
+
+    let mut received = 0;
+
+    loop {
+        let message = recv(&mut client).await;
+
+        if message["type"] == "reset" {
+            break;
+        }
+
+        received += 1;
+        assert!(received <= SUBSCRIBER_CAPACITY, "no reset after {} messages", received);
+    }
+
+    client.close().await.unwrap();
+}
@@ -25,6 +25,21 @@ pub struct AuthUser {
 #[derive(Debug)]
 pub struct MaybeAuthUser(pub Option<AuthUser>);
 
+/// Media type clients can list in `Accept` to get `bodyHtml` with articles.
+const BODY_HTML_MEDIA_TYPE: &str = "application/vnd.conduit.html+json";
+
+/// Whether the client opted into rendered article bodies, either with `?bodyHtml=true` or
+/// through `Accept: application/vnd.conduit.html+json`.
+#[derive(Debug, Clone, Copy)]
+pub struct WantsBodyHtml(pub bool);
+
+/// The `If-Match` and `If-None-Match` headers of a request.
+#[derive(Debug)]
+pub struct Preconditions {
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 struct AuthUserClaims {
     user_id: Uuid,
@@ -44,7 +59,10 @@ impl AuthUser {
         .expect("HMAC signing should be infallible")
     }
 
-    fn from_authorization(ctx: &ApiContext, auth_header: &HeaderValue) -> Result<Self, Error> {
+    pub(in crate::http) fn from_authorization(
+        ctx: &ApiContext,
+        auth_header: &HeaderValue,
+    ) -> Result<Self, Error> {
         let auth_header = auth_header.to_str().map_err(|_| {
             log::debug!("Authorization header is not UTF-8");
             Error::Unauthorized
@@ -58,15 +76,14 @@ impl AuthUser {
             return Err(Error::Unauthorized);
         }
 
-        let token = &auth_header[SCHEME_PREFIX.len()..];
+        Self::from_token(ctx, &auth_header[SCHEME_PREFIX.len()..])
+    }
 
+    /// Verifies a token as handed out by the user endpoints, without the `Token ` prefix.
+    pub(in crate::http) fn from_token(ctx: &ApiContext, token: &str) -> Result<Self, Error> {
         let jwt =
             jwt::Token::<jwt::Header, AuthUserClaims, _>::parse_unverified(token).map_err(|e| {
-                log::debug!(
-                    "Failed to parse athorization header {:?}: {}",
-                    auth_header,
-                    e
-                );
+                log::debug!("Failed to parse authorization token {:?}: {}", token, e);
                 Error::Unauthorized
             })?;
 
@@ -97,6 +114,47 @@ impl MaybeAuthUser {
     }
 }
 
+impl Preconditions {
+    /// Checks `If-Match` against the current strong `etag` of the resource.
+    ///
+    /// When `required` is set, a request without `If-Match` is rejected as well.
+    pub fn check_if_match(&self, etag: &str, required: bool) -> Result<(), Error> {
+        match &self.if_match {
+            Some(if_match) if !etag_list_matches(if_match, etag, false) => {
+                Err(Error::PreconditionFailed)
+            }
+            Some(_) => Ok(()),
+            None if required => Err(Error::PreconditionRequired),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if `If-None-Match` names the current `etag`, i.e. the client's cached
+    /// copy is still fresh.
+    pub fn is_not_modified(&self, etag: &str) -> bool {
+        self.if_none_match
+            .as_deref()
+            .map_or(false, |if_none_match| etag_list_matches(if_none_match, etag, true))
+    }
+}
+
+/// Matches a comma-separated list of entity tags (or `*`) against `etag`.
+///
+/// `If-Match` uses strong comparison, so weak tags never match there; `If-None-Match` uses
+/// weak comparison and ignores the `W/` prefix.
+fn etag_list_matches(list: &str, etag: &str, weak: bool) -> bool {
+    list.split(',').map(str::trim).any(|candidate| {
+        if candidate == "*" {
+            return true;
+        }
+
+        match candidate.strip_prefix("W/") {
+            Some(candidate) => weak && candidate == etag,
+            None => candidate == etag,
+        }
+    })
+}
+
 #[async_trait]
 impl FromRequest for AuthUser {
     type Rejection = Error;
@@ -134,4 +192,48 @@ impl FromRequest for MaybeAuthUser {
                 .transpose()?,
         ))
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl FromRequest for Preconditions {
+    type Rejection = Error;
+
+    async fn from_request(req: &mut RequestParts<Body>) -> Result<Self, Self::Rejection> {
+        let header_string = |name| {
+            req.headers()
+                .and_then(|headers| headers.get(name))
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Ok(Self {
+            if_match: header_string(header::IF_MATCH),
+            if_none_match: header_string(header::IF_NONE_MATCH),
+        })
+    }
+}
+
+#[async_trait]
+impl FromRequest for WantsBodyHtml {
+    type Rejection = Error;
+
+    async fn from_request(req: &mut RequestParts<Body>) -> Result<Self, Self::Rejection> {
+        let in_query = req
+            .uri()
+            .query()
+            .and_then(|query| serde_urlencoded::from_str::<Vec<(String, String)>>(query).ok())
+            .map_or(false, |params| {
+                params
+                    .iter()
+                    .any(|(key, value)| key == "bodyHtml" && (value == "true" || value == "1"))
+            });
+
+        let in_accept = req
+            .headers()
+            .and_then(|headers| headers.get(header::ACCEPT))
+            .and_then(|accept| accept.to_str().ok())
+            .map_or(false, |accept| accept.contains(BODY_HTML_MEDIA_TYPE));
+
+        Ok(Self(in_query || in_accept))
+    }
+}
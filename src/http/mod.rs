@@ -12,8 +12,15 @@ mod extractor;
 mod users;
 mod profiles;
 mod articles;
+mod events;
+mod notifications;
 mod types;
+mod webhooks;
 
+#[cfg(test)]
+mod test_support;
+
+pub use articles::recount_article_counters;
 pub use error::{Error, ResultExt};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -22,26 +29,45 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 struct ApiContext {
     config: Arc<Config>,
     db: MySqlPool,
+    events: events::EventBus,
 }
 
 pub async fn serve(config: Config, db: MySqlPool) -> anyhow::Result<()> {
-    let app = api_router().layer(
-        ServiceBuilder::new()
-            .layer(AddExtensionLayer::new(ApiContext {
-                config: Arc::new(config),
-                db,
-            }))
-            .layer(TraceLayer::new_for_http()),
+    let events = events::EventBus::new();
+
+    articles::spawn_publish_scheduler(db.clone(), events.clone());
+    articles::spawn_trash_purger(db.clone(), config.trash_retention_days);
+    webhooks::spawn_webhook_worker(
+        db.clone(),
+        config.webhook_max_attempts,
+        config.webhook_allow_private_networks,
     );
 
+    let app = app_with_events(config, db, events);
+
     axum::Server::bind(&"0.0.0.0:8080".parse()?)
         .serve(app.into_make_service())
         .await
         .context("error running HTTP server")
 }
 
+fn app_with_events(config: Config, db: MySqlPool, events: events::EventBus) -> Router {
+    api_router().layer(
+        ServiceBuilder::new()
+            .layer(AddExtensionLayer::new(ApiContext {
+                config: Arc::new(config),
+                db,
+                events,
+            }))
+            .layer(TraceLayer::new_for_http()),
+    )
+}
+
 fn api_router() -> Router {
     users::router()
         .merge(profiles::router())
         .merge(articles::router())
+        .merge(notifications::router())
+        .merge(events::router())
+        .merge(webhooks::router())
 }
@@ -0,0 +1,463 @@
+use axum::{extract::{Extension, Query}, Json, Router, routing::{get, post}};
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use super::{events::Topic, extractor::AuthUser, profiles::Profile, types::{DbBool, DbUuid, Timestamptz}, ApiContext, Error, Result};
+
+const DEFAULT_NOTIFICATION_LIMIT: i64 = 20;
+
+const MAX_NOTIFICATION_LIMIT: i64 = 100;
+
+/// Notifications `mark_read` accepts at once.
+const MAX_MARK_READ: usize = 100;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/notifications", get(list_notifications))
+        .route("/api/notifications/read", post(mark_read))
+        .route("/api/notifications/read-all", post(mark_all_read))
+        .route(
+            "/api/notifications/preferences",
+            get(get_preferences).put(update_preferences),
+        )
+}
+
+#[derive(sqlx::Type, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    /// Someone followed the user.
+    Follow,
+    /// Someone favorited one of the user's articles.
+    Favorite,
+    /// Someone commented on one of the user's articles.
+    Comment,
+    /// Someone mentioned the user in an article or a comment.
+    Mention,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct ListNotificationsQuery {
+    /// Only return notifications that haven't been read yet.
+    unread: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultipleNotificationsBody {
+    notifications: Vec<Notification>,
+    unread_count: i64,
+}
+
+#[derive(serde::Serialize)]
+struct NotificationBody {
+    notification: Notification,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UnreadCountBody {
+    unread_count: i64,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    id: i64,
+    kind: NotificationKind,
+    actor: Profile,
+    article: Option<NotificationArticle>,
+    comment_id: Option<i64>,
+    read: bool,
+    created_at: Timestamptz,
+}
+
+struct NotificationFromQuery {
+    notification_id: i64,
+    user_id: DbUuid,
+    kind: NotificationKind,
+    actor_username: String,
+    actor_bio: String,
+    actor_image: Option<String>,
+    following_actor: DbBool,
+    article_slug: Option<String>,
+    article_title: Option<String>,
+    comment_id: Option<i64>,
+    read: DbBool,
+    created_at: Timestamptz,
+}
+
+impl NotificationFromQuery {
+    fn into_notification(self) -> Notification {
+        Notification {
+            id: self.notification_id,
+            kind: self.kind,
+            actor: Profile {
+                username: self.actor_username,
+                bio: self.actor_bio,
+                image: self.actor_image,
+                following: self.following_actor,
+            },
+            article: self
+                .article_slug
+                .zip(self.article_title)
+                .map(|(slug, title)| NotificationArticle { slug, title }),
+            comment_id: self.comment_id,
+            read: self.read.into(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct NotificationArticle {
+    slug: String,
+    title: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarkRead {
+    notification_ids: Vec<i64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PreferencesBody<T = Preferences> {
+    preferences: T,
+}
+
+/// Which kinds of notifications the user receives.
+#[derive(serde::Serialize)]
+struct Preferences {
+    follow: bool,
+    favorite: bool,
+    comment: bool,
+    mention: bool,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct UpdatePreferences {
+    follow: Option<bool>,
+    favorite: Option<bool>,
+    comment: Option<bool>,
+    mention: Option<bool>,
+}
+
+/// Tells `user_id` that `actor_id` did something, unless it's about their own doing or they
+/// turned this kind of notification off. Runs in the transaction of the action itself, so a
+/// notification exists exactly when the action happened.
+///
+/// Returns the ID of the new notification, to be handed to `publish_notifications` once the
+/// transaction is committed.
+pub(super) async fn notify(
+    tx: &mut Transaction<'_, MySql>,
+    user_id: Uuid,
+    actor_id: Uuid,
+    kind: NotificationKind,
+    article_id: Option<Uuid>,
+    comment_id: Option<i64>,
+) -> Result<Option<i64>> {
+    if user_id == actor_id {
+        return Ok(None);
+    }
+
+    let result = sqlx::query!(
+        r#"
+insert into notification (user_id, actor_user_id, kind, article_id, comment_id)
+select ?, ?, ?, ?, ? from dual
+where not exists(select 1 from notification_mute where user_id = ? and kind = ?)
+        "#,
+        DbUuid(user_id),
+        DbUuid(actor_id),
+        kind,
+        article_id.map(DbUuid),
+        comment_id,
+        DbUuid(user_id),
+        kind
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    Ok((result.rows_affected() > 0).then(|| result.last_insert_id() as i64))
+}
+
+/// Streams notifications created by `notify` to their recipients, except those
+/// `list_notifications` would hide.
+///
+/// Called after the action's transaction committed, so failing here doesn't fail the request:
+/// the notifications are in the inbox either way.
+pub(super) async fn publish_notifications(ctx: &ApiContext, notification_ids: &[i64]) {
+    for &notification_id in notification_ids {
+        let notification = sqlx::query_as!(
+            NotificationFromQuery,
+            r#"
+select
+    notification.notification_id,
+    notification.user_id `user_id: DbUuid`,
+    notification.kind `kind: NotificationKind`,
+    actor.username actor_username,
+    actor.bio actor_bio,
+    actor.image actor_image,
+    exists(
+        select 1 from follow
+        where followed_user_id = actor.user_id and following_user_id = notification.user_id
+    ) `following_actor!: DbBool`,
+    article.slug `article_slug?`,
+    article.title `article_title?`,
+    notification.comment_id,
+    notification.read_at is not null `read!: DbBool`,
+    notification.created_at `created_at: Timestamptz`
+from notification
+inner join user actor on actor.user_id = notification.actor_user_id
+left join article on article.article_id = notification.article_id
+left join article_comment comment on comment.comment_id = notification.comment_id
+where notification.notification_id = ?
+and (
+    notification.article_id is null
+    or (
+        article.deleted_at is null
+        and (article.status = 'published' or article.user_id = notification.user_id)
+    )
+)
+and (notification.comment_id is null or comment.deleted_at is null)
+            "#,
+            notification_id
+        )
+            .fetch_optional(&ctx.db)
+            .await;
+
+        let notification = match notification {
+            Ok(Some(notification)) => notification,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("failed to load notification {} to publish: {}", notification_id, e);
+                continue;
+            }
+        };
+
+        ctx.events.publish(
+            Topic::User(notification.user_id.0),
+            "notification",
+            &NotificationBody {
+                notification: notification.into_notification(),
+            },
+        );
+    }
+}
+
+/// Notifications about articles and comments that are gone or hidden from the user are
+/// skipped, and don't count as unread either.
+async fn list_notifications(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Query(query): Query<ListNotificationsQuery>,
+) -> Result<Json<MultipleNotificationsBody>> {
+    let notifications = sqlx::query_as!(
+        NotificationFromQuery,
+        r#"
+select
+    notification.notification_id,
+    notification.user_id `user_id: DbUuid`,
+    notification.kind `kind: NotificationKind`,
+    actor.username actor_username,
+    actor.bio actor_bio,
+    actor.image actor_image,
+    exists(
+        select 1 from follow
+        where followed_user_id = actor.user_id and following_user_id = notification.user_id
+    ) `following_actor!: DbBool`,
+    article.slug `article_slug?`,
+    article.title `article_title?`,
+    notification.comment_id,
+    notification.read_at is not null `read!: DbBool`,
+    notification.created_at `created_at: Timestamptz`
+from notification
+inner join user actor on actor.user_id = notification.actor_user_id
+left join article on article.article_id = notification.article_id
+left join article_comment comment on comment.comment_id = notification.comment_id
+where notification.user_id = ?
+and (? = false or notification.read_at is null)
+and (
+    notification.article_id is null
+    or (
+        article.deleted_at is null
+        and (article.status = 'published' or article.user_id = notification.user_id)
+    )
+)
+and (notification.comment_id is null or comment.deleted_at is null)
+order by notification.notification_id desc
+limit ? offset ?
+        "#,
+        DbUuid(auth_user.user_id),
+        query.unread,
+        query
+            .limit
+            .unwrap_or(DEFAULT_NOTIFICATION_LIMIT)
+            .clamp(1, MAX_NOTIFICATION_LIMIT),
+        query.offset.unwrap_or(0).max(0)
+    )
+        .fetch_all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(NotificationFromQuery::into_notification)
+        .collect();
+
+    let unread_count = unread_count(&ctx, auth_user.user_id).await?;
+
+    Ok(Json(MultipleNotificationsBody {
+        notifications,
+        unread_count,
+    }))
+}
+
+async fn mark_read(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Json(req): Json<MarkRead>,
+) -> Result<Json<UnreadCountBody>> {
+    if req.notification_ids.len() > MAX_MARK_READ {
+        return Err(Error::unprocessable_entity([(
+            "notificationIds",
+            format!("at most {} notifications can be marked at once", MAX_MARK_READ),
+        )]));
+    }
+
+    let mut tx = ctx.db.begin().await?;
+
+    for notification_id in req.notification_ids {
+        sqlx::query!(
+            r#"
+update notification set read_at = current_timestamp
+where notification_id = ? and user_id = ? and read_at is null
+            "#,
+            notification_id,
+            DbUuid(auth_user.user_id)
+        )
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    let unread_count = unread_count(&ctx, auth_user.user_id).await?;
+
+    Ok(Json(UnreadCountBody { unread_count }))
+}
+
+async fn mark_all_read(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+) -> Result<Json<UnreadCountBody>> {
+    sqlx::query!(
+        r#"
+update notification set read_at = current_timestamp where user_id = ? and read_at is null
+        "#,
+        DbUuid(auth_user.user_id)
+    )
+        .execute(&ctx.db)
+        .await?;
+
+    Ok(Json(UnreadCountBody { unread_count: 0 }))
+}
+
+async fn get_preferences(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+) -> Result<Json<PreferencesBody>> {
+    let preferences = preferences(&ctx, auth_user.user_id).await?;
+
+    Ok(Json(PreferencesBody { preferences }))
+}
+
+async fn update_preferences(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Json(req): Json<PreferencesBody<UpdatePreferences>>,
+) -> Result<Json<PreferencesBody>> {
+    let changes = [
+        (NotificationKind::Follow, req.preferences.follow),
+        (NotificationKind::Favorite, req.preferences.favorite),
+        (NotificationKind::Comment, req.preferences.comment),
+        (NotificationKind::Mention, req.preferences.mention),
+    ];
+
+    let mut tx = ctx.db.begin().await?;
+
+    for (kind, enabled) in changes {
+        match enabled {
+            Some(true) => {
+                sqlx::query!(
+                    "delete from notification_mute where user_id = ? and kind = ?",
+                    DbUuid(auth_user.user_id),
+                    kind
+                )
+                    .execute(&mut tx)
+                    .await?;
+            }
+            Some(false) => {
+                sqlx::query!(
+                    "insert ignore into notification_mute (user_id, kind) values (?, ?)",
+                    DbUuid(auth_user.user_id),
+                    kind
+                )
+                    .execute(&mut tx)
+                    .await?;
+            }
+            None => (),
+        }
+    }
+
+    tx.commit().await?;
+
+    let preferences = preferences(&ctx, auth_user.user_id).await?;
+
+    Ok(Json(PreferencesBody { preferences }))
+}
+
+async fn preferences(ctx: &ApiContext, user_id: Uuid) -> Result<Preferences> {
+    let muted = sqlx::query_scalar!(
+        r#"
+select kind `kind: NotificationKind` from notification_mute where user_id = ?
+        "#,
+        DbUuid(user_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Preferences {
+        follow: !muted.contains(&NotificationKind::Follow),
+        favorite: !muted.contains(&NotificationKind::Favorite),
+        comment: !muted.contains(&NotificationKind::Comment),
+        mention: !muted.contains(&NotificationKind::Mention),
+    })
+}
+
+async fn unread_count(ctx: &ApiContext, user_id: Uuid) -> Result<i64> {
+    let unread_count = sqlx::query_scalar!(
+        r#"
+select count(*)
+from notification
+left join article on article.article_id = notification.article_id
+left join article_comment comment on comment.comment_id = notification.comment_id
+where notification.user_id = ?
+and notification.read_at is null
+and (
+    notification.article_id is null
+    or (
+        article.deleted_at is null
+        and (article.status = 'published' or article.user_id = notification.user_id)
+    )
+)
+and (notification.comment_id is null or comment.deleted_at is null)
+        "#,
+        DbUuid(user_id)
+    )
+        .fetch_one(&ctx.db)
+        .await?;
+
+    Ok(unread_count)
+}
@@ -1,9 +1,14 @@
-use axum::{extract::{Extension, Path}, Json, Router, routing::{get, post}};
+use axum::{extract::{Extension, Path, Query}, Json, Router, routing::{get, post}};
 
-use super::{extractor::{MaybeAuthUser, AuthUser}, ApiContext, Error, Result, types::DbBool};
+use super::{extractor::{MaybeAuthUser, AuthUser}, notifications::{notify, publish_notifications, NotificationKind}, webhooks::{self, WebhookEvent}, ApiContext, Error, Result, types::{DbBool, DbUuid}};
+
+const DEFAULT_SEARCH_LIMIT: i64 = 10;
+
+const MAX_SEARCH_LIMIT: i64 = 50;
 
 pub fn router() -> Router {
     Router::new()
+        .route("/api/profiles/search", get(search_profiles))
         .route("/api/profiles/:username", get(get_user_profile))
         .route(
             "/api/profiles/:username/follow",
@@ -17,6 +22,24 @@ struct ProfileBody {
     profile: Profile,
 }
 
+#[derive(serde::Serialize)]
+struct MultipleProfilesBody {
+    profiles: Vec<Profile>,
+}
+
+/// Data of `user.followed` webhook events, by username.
+#[derive(serde::Serialize)]
+struct UserFollowedEvent<'a> {
+    follower: &'a str,
+    followed: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+struct SearchProfilesQuery {
+    q: String,
+    limit: Option<i64>,
+}
+
 #[derive(serde::Serialize)]
 pub struct Profile {
     pub username: String,
@@ -39,7 +62,7 @@ select username, bio, image, exists(
 from user
 where username = ?
         "#,
-        maybe_auth_user.user_id().map(|id| id.to_string()),
+        maybe_auth_user.user_id().map(DbUuid),
         username,
     )
         .fetch_optional(&ctx.db)
@@ -49,6 +72,71 @@ where username = ?
     Ok(Json(ProfileBody{ profile }))
 }
 
+/// Exact username matches rank first, then username prefix matches, then users whose bio
+/// matches by relevance.
+async fn search_profiles(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: Extension<ApiContext>,
+    query: Query<SearchProfilesQuery>,
+) -> Result<Json<MultipleProfilesBody>> {
+    let q = query.q.trim();
+
+    if q.is_empty() {
+        return Err(Error::unprocessable_entity([("q", "can't be blank")]));
+    }
+
+    let prefix = format!("{}%", escape_like(q));
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let profiles = sqlx::query_as!(
+        Profile,
+        r#"
+select username, bio, image, exists(
+    select 1 from follow where followed_user_id = user.user_id and following_user_id = ?
+) `following!:_`
+from user
+where username like ? or match(bio) against (? in natural language mode) > 0
+order by
+    case
+        when username = ? then 0
+        when username like ? then 1
+        else 2
+    end,
+    match(bio) against (? in natural language mode) desc,
+    username
+limit ?
+        "#,
+        maybe_auth_user.user_id().map(DbUuid),
+        prefix,
+        q,
+        q,
+        prefix,
+        q,
+        limit
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(Json(MultipleProfilesBody { profiles }))
+}
+
+/// Escapes the wildcard characters of a `like` pattern.
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
 async fn follow_user(
     auth_user: AuthUser,
     ctx: Extension<ApiContext>,
@@ -58,7 +146,7 @@ async fn follow_user(
 
     let user = sqlx::query!(
         r#"
-select user_id, username, bio, image from user where username = ?
+select user_id `user_id: DbUuid`, username, bio, image from user where username = ?
         "#,
         username
     )
@@ -66,22 +154,51 @@ select user_id, username, bio, image from user where username = ?
         .await?
         .ok_or(Error::NotFound)?;
 
-    if user.user_id == auth_user.user_id.to_string() {
+    if user.user_id.0 == auth_user.user_id {
         return Err(Error::Forbidden);
     }
 
-    sqlx::query!(
+    let inserted = sqlx::query!(
         r#"
 insert ignore into follow(following_user_id, followed_user_id) values (?, ?)
         "#,
-        auth_user.user_id.to_string(),
+        DbUuid(auth_user.user_id),
         user.user_id
     )
     .execute(&mut tx)
-    .await?;
+    .await?
+    .rows_affected();
+
+    let notified = if inserted > 0 {
+        let follower: String = sqlx::query_scalar!(
+            r#"
+select username from user where user_id = ?
+            "#,
+            DbUuid(auth_user.user_id)
+        )
+            .fetch_one(&mut tx)
+            .await?;
+
+        webhooks::enqueue(
+            &mut tx,
+            WebhookEvent::UserFollowed,
+            user.user_id.0,
+            &UserFollowedEvent {
+                follower: &follower,
+                followed: &user.username,
+            },
+        )
+            .await?;
+
+        notify(&mut tx, user.user_id.0, auth_user.user_id, NotificationKind::Follow, None, None).await?
+    } else {
+        None
+    };
 
     tx.commit().await?;
 
+    publish_notifications(&ctx, notified.as_slice()).await;
+
     Ok(Json(ProfileBody { 
         profile: Profile {
             username: user.username,
@@ -101,7 +218,7 @@ async fn unfollow_user(
 
     let user = sqlx::query!(
         r#"
-select user_id, username, bio, image from user where username = ?
+select user_id `user_id: DbUuid`, username, bio, image from user where username = ?
         "#,
         username
     )
@@ -113,8 +230,8 @@ select user_id, username, bio, image from user where username = ?
         r#"
 delete from follow where following_user_id = ? and followed_user_id = ?
         "#,
-        auth_user.user_id.to_string(),
-        user.user_id.to_string()
+        DbUuid(auth_user.user_id),
+        user.user_id
     )
         .execute(&mut tx)
         .await?;
@@ -0,0 +1,159 @@
+//! Helpers for the tests driving the API end to end.
+//!
+//! Tests need a MySQL database at `DATABASE_URL` (`.env` is read as well). Migrations are run
+//! on it, and every user they register gets a unique name, so they can share the database with
+//! each other and with earlier runs.
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use clap::Parser;
+use serde_json::{json, Value};
+use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+use super::{app_with_events, events::EventBus};
+
+pub struct TestApp {
+    pub router: Router,
+    pub db: MySqlPool,
+    pub events: EventBus,
+}
+
+pub struct TestUser {
+    pub username: String,
+    pub token: String,
+}
+
+impl TestApp {
+    /// The API with its default configuration, save for `args` given as on the command line.
+    pub async fn new(args: &[&str]) -> anyhow::Result<Self> {
+        dotenv::dotenv().ok();
+
+        let config = Config::try_parse_from(
+            ["conduit", "--hmac-key", "test-hmac-key"]
+                .iter()
+                .chain(args),
+        )?;
+
+        let db = MySqlPoolOptions::new()
+            .max_connections(5)
+            .connect(&config.database_url)
+            .await?;
+
+        sqlx::migrate!().run(&db).await?;
+
+        let events = EventBus::new();
+
+        Ok(Self {
+            router: app_with_events(config, db.clone(), events.clone()),
+            db,
+            events,
+        })
+    }
+
+    /// Sends a request as the user `token` was issued to, returning the status and JSON body
+    /// (`null` if there is none).
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> anyhow::Result<(StatusCode, Value)> {
+        let mut request = Request::builder().method(method).uri(uri);
+
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Token {}", token));
+        }
+
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))?,
+            None => request.body(Body::empty())?,
+        };
+
+        let response = self.router.clone().oneshot(request).await?;
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await?;
+
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes)?
+        };
+
+        Ok((status, body))
+    }
+
+    pub async fn register_user(&self) -> anyhow::Result<TestUser> {
+        let username = format!("test-{}", &Uuid::new_v4().to_simple().to_string()[..16]);
+
+        let (status, body) = self
+            .request(
+                Method::POST,
+                "/api/users",
+                None,
+                Some(json!({
+                    "user": {
+                        "username": username,
+                        "email": format!("{}@example.com", username),
+                        "password": "correct horse battery staple",
+                    }
+                })),
+            )
+            .await?;
+
+        anyhow::ensure!(status == StatusCode::OK, "registering failed: {} {}", status, body);
+
+        let token = body["user"]["token"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("no token in {}", body))?
+            .to_string();
+
+        Ok(TestUser { username, token })
+    }
+
+    /// Publishes an article by `user`, returning its slug.
+    pub async fn create_article(&self, user: &TestUser) -> anyhow::Result<String> {
+        let (status, body) = self
+            .request(
+                Method::POST,
+                "/api/articles",
+                Some(&user.token),
+                Some(json!({
+                    "article": {
+                        "title": format!("Notes of {}", user.username),
+                        "description": "A test article",
+                        "body": "Nothing to see here.",
+                        "tag_list": [],
+                    }
+                })),
+            )
+            .await?;
+
+        anyhow::ensure!(status == StatusCode::OK, "creating article failed: {} {}", status, body);
+
+        Ok(body["article"]["slug"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("no slug in {}", body))?
+            .to_string())
+    }
+
+    pub async fn article_id(&self, slug: &str) -> anyhow::Result<Uuid> {
+        let (article_id,) = sqlx::query_as::<_, (super::types::DbUuid,)>(
+            "select article_id from article where slug = ?",
+        )
+            .bind(slug)
+            .fetch_one(&self.db)
+            .await?;
+
+        Ok(article_id.0)
+    }
+}
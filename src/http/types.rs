@@ -1,10 +1,11 @@
 use std::fmt::Formatter;
 
 use serde::{Serialize, Deserialize, de::Visitor};
-use sqlx::{Database, Decode, database::HasValueRef, error::BoxDynError, Type, mysql::MySqlTypeInfo, MySql};
+use sqlx::{Database, Decode, Encode, encode::IsNull, database::HasValueRef, error::BoxDynError, Type, mysql::{MySqlTypeInfo, MySqlValueRef}, MySql};
 use time::{OffsetDateTime, Format};
+use uuid::Uuid;
 
-#[derive(sqlx::Type)]
+#[derive(sqlx::Type, Clone, Copy)]
 pub struct Timestamptz(pub OffsetDateTime);
 
 impl Serialize for Timestamptz {
@@ -44,7 +45,7 @@ impl<'de> Deserialize<'de> for Timestamptz {
     }
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, Clone, Copy)]
 pub struct DbBool(bool);
 
 impl From<DbBool> for bool {
@@ -77,4 +78,59 @@ impl Type<MySql> for DbBool {
     fn compatible(ty: &MySqlTypeInfo) -> bool {
         <i64 as Type<MySql>>::compatible(ty)
     }
-}
\ No newline at end of file
+}
+
+/// A `Uuid` stored as `BINARY(16)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbUuid(pub Uuid);
+
+impl DbUuid {
+    /// Generates a random UUID whose first 48 bits are the current Unix time in milliseconds,
+    /// laid out like a version 7 UUID. New rows then land at the end of the primary key index
+    /// instead of splitting pages all over it.
+    pub fn new_ordered() -> Self {
+        let millis = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+
+        let mut bytes = *Uuid::new_v4().as_bytes();
+        bytes[..6].copy_from_slice(&millis.to_be_bytes()[10..]);
+        bytes[6] = (bytes[6] & 0x0f) | 0x70;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        Self(Uuid::from_bytes(bytes))
+    }
+}
+
+impl From<DbUuid> for Uuid {
+    fn from(id: DbUuid) -> Self {
+        id.0
+    }
+}
+
+impl From<Uuid> for DbUuid {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl Encode<'_, MySql> for DbUuid {
+    fn encode_by_ref(&self, buf: &mut Vec<u8>) -> IsNull {
+        <&[u8] as Encode<MySql>>::encode(self.0.as_bytes(), buf)
+    }
+}
+
+impl Decode<'_, MySql> for DbUuid {
+    fn decode(value: MySqlValueRef<'_>) -> Result<Self, BoxDynError> {
+        let bytes = <&[u8] as Decode<MySql>>::decode(value)?;
+        Ok(DbUuid(Uuid::from_slice(bytes)?))
+    }
+}
+
+impl Type<MySql> for DbUuid {
+    fn type_info() -> MySqlTypeInfo {
+        <[u8] as Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        <[u8] as Type<MySql>>::compatible(ty)
+    }
+}
@@ -1,11 +1,8 @@
-use std::str::FromStr;
-
 use anyhow::{Context};
 use argon2::{password_hash::SaltString, PasswordHash, Argon2};
 use axum::{extract::Extension, Json, Router, routing::{post, get}};
-use uuid::Uuid;
 
-use super::{ApiContext, Result, ResultExt, Error, extractor::AuthUser};
+use super::{ApiContext, Result, ResultExt, Error, extractor::AuthUser, types::DbUuid};
 
 pub fn router() -> Router {
     Router::new()
@@ -57,12 +54,12 @@ async fn create_user(
 ) -> Result<Json<UserBody<User>>> {
     let password_hash = hash_password(req.user.password).await?;
     
-    let user_id = Uuid::new_v4();
+    let user_id = DbUuid::new_ordered();
     sqlx::query!(
         r#"
 insert into user (user_id, username, email, password_hash) values (?, ?, ?, ?)
         "#,
-        user_id.to_string(),
+        user_id,
         req.user.username,
         req.user.email,
         password_hash,
@@ -78,7 +75,7 @@ insert into user (user_id, username, email, password_hash) values (?, ?, ?, ?)
     Ok(Json(UserBody {
         user: User {
             email: req.user.email,
-            token: AuthUser { user_id }.to_jwt(&ctx),
+            token: AuthUser { user_id: user_id.0 }.to_jwt(&ctx),
             username: req.user.username,
             bio: "".to_string(),
             image: None,
@@ -92,7 +89,7 @@ async fn login_user(
 ) -> Result<Json<UserBody<User>>> {
     let user = sqlx::query!(
         r#"
-select user_id, email, username, bio, image, password_hash
+select user_id `user_id: DbUuid`, email, username, bio, image, password_hash
 from user where email = ?
         "#,
         req.user.email,
@@ -107,7 +104,7 @@ from user where email = ?
         user: User {
             email: user.email,
             token: AuthUser {
-                user_id: Uuid::from_str(&user.user_id).unwrap(),
+                user_id: user.user_id.into(),
             }
             .to_jwt(&ctx),
             username: user.username,
@@ -149,7 +146,7 @@ where user_id = ?
         password_hash,
         req.user.bio,
         req.user.image,
-        auth_user.user_id.to_string()
+        DbUuid(auth_user.user_id)
     )
         .execute(&mut tx)
         .await
@@ -164,7 +161,7 @@ where user_id = ?
         r#"
 select email, username, bio, image from user where user_id = ?
         "#,
-        auth_user.user_id.to_string()
+        DbUuid(auth_user.user_id)
     )
         .fetch_one(&mut tx)
         .await?;
@@ -190,7 +187,7 @@ async fn get_current_user(
         r#"
 select email, username, bio, image from user where user_id = ?
         "#,
-        auth_user.user_id.to_string()
+        DbUuid(auth_user.user_id)
     )
     .fetch_one(&ctx.db)
     .await?;
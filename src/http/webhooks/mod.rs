@@ -0,0 +1,569 @@
+//! Outgoing webhooks.
+//!
+//! Handlers record events in `webhook_event` as part of their transaction (see
+//! [`enqueue`]), and the worker turns them into signed POST requests to every subscribed
+//! webhook, retrying failed ones. Each webhook keeps a log of its deliveries, any of which
+//! its owner can replay.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    routing::{get, post},
+    Json, Router,
+};
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::http::{
+    extractor::AuthUser,
+    types::{DbUuid, Timestamptz},
+    ApiContext, Error, Result,
+};
+
+mod network;
+mod outbox;
+mod worker;
+
+#[cfg(test)]
+mod stub;
+
+#[cfg(test)]
+mod tests;
+
+pub(in crate::http) use outbox::enqueue;
+pub(in crate::http) use worker::spawn_webhook_worker;
+pub use outbox::WebhookEvent;
+
+const DEFAULT_DELIVERY_LIMIT: i64 = 20;
+
+const MAX_DELIVERY_LIMIT: i64 = 100;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route(
+            "/api/webhooks/:webhook_id",
+            get(get_webhook).put(update_webhook).delete(delete_webhook),
+        )
+        .route("/api/webhooks/:webhook_id/deliveries", get(list_deliveries))
+        .route(
+            "/api/webhooks/:webhook_id/deliveries/:delivery_id/replay",
+            post(replay_delivery),
+        )
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct WebhookBody<T = Webhook> {
+    webhook: T,
+}
+
+#[derive(serde::Serialize)]
+struct MultipleWebhooksBody {
+    webhooks: Vec<Webhook>,
+}
+
+#[derive(serde::Deserialize)]
+struct CreateWebhook {
+    url: String,
+    events: Vec<WebhookEvent>,
+    /// Receive the events of all users instead of only the owner's. Admins only.
+    #[serde(default)]
+    global: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct UpdateWebhook {
+    url: Option<String>,
+    events: Option<Vec<WebhookEvent>>,
+    active: Option<bool>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Webhook {
+    id: Uuid,
+    url: String,
+    events: Vec<WebhookEvent>,
+    global: bool,
+    active: bool,
+    /// The key payloads are signed with; only returned when the webhook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    created_at: Timestamptz,
+    updated_at: Timestamptz,
+}
+
+struct WebhookFromQuery {
+    webhook_id: DbUuid,
+    user_id: DbUuid,
+    url: String,
+    events: serde_json::Value,
+    global: bool,
+    active: bool,
+    created_at: Timestamptz,
+    updated_at: Timestamptz,
+}
+
+impl WebhookFromQuery {
+    fn into_webhook(self) -> Webhook {
+        Webhook {
+            id: self.webhook_id.0,
+            url: self.url,
+            events: serde_json::from_value(self.events).unwrap_or_default(),
+            global: self.global,
+            active: self.active,
+            secret: None,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct ListDeliveriesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+struct MultipleDeliveriesBody {
+    deliveries: Vec<Delivery>,
+}
+
+#[derive(serde::Serialize)]
+struct DeliveryBody {
+    delivery: Delivery,
+}
+
+#[derive(sqlx::Type, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Every attempt failed; only a replay sends it again.
+    Failed,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Delivery {
+    id: i64,
+    event_id: i64,
+    event: WebhookEvent,
+    status: DeliveryStatus,
+    /// When the next attempt is due, while `pending`.
+    next_attempt_at: Option<Timestamptz>,
+    delivered_at: Option<Timestamptz>,
+    created_at: Timestamptz,
+    /// Oldest first.
+    attempts: Vec<Attempt>,
+}
+
+struct DeliveryFromQuery {
+    delivery_id: i64,
+    event_id: i64,
+    event: WebhookEvent,
+    status: DeliveryStatus,
+    next_attempt_at: Timestamptz,
+    delivered_at: Option<Timestamptz>,
+    created_at: Timestamptz,
+}
+
+impl DeliveryFromQuery {
+    fn into_delivery(self, attempts: Vec<Attempt>) -> Delivery {
+        Delivery {
+            id: self.delivery_id,
+            event_id: self.event_id,
+            event: self.event,
+            status: self.status,
+            next_attempt_at: (self.status == DeliveryStatus::Pending)
+                .then(|| self.next_attempt_at),
+            delivered_at: self.delivered_at,
+            created_at: self.created_at,
+            attempts,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Attempt {
+    /// `None` if no response was received.
+    status_code: Option<i16>,
+    error: Option<String>,
+    duration_ms: i32,
+    created_at: Timestamptz,
+}
+
+struct AttemptFromQuery {
+    delivery_id: i64,
+    status_code: Option<i16>,
+    error: Option<String>,
+    duration_ms: i32,
+    created_at: Timestamptz,
+}
+
+/// Checks that `url` can be used for a webhook. Unless the configuration allows it, its host
+/// must only resolve to public addresses; the worker checks them again when sending.
+async fn validate_url(ctx: &ApiContext, url: &str) -> Result<()> {
+    let url = match reqwest::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => url,
+        _ => {
+            return Err(Error::unprocessable_entity([(
+                "url",
+                "must be an absolute http or https URL",
+            )]))
+        }
+    };
+
+    if !ctx.config.webhook_allow_private_networks && !network::resolves_to_public(&url).await {
+        return Err(Error::unprocessable_entity([(
+            "url",
+            "must only resolve to public addresses",
+        )]));
+    }
+
+    Ok(())
+}
+
+/// Sorts and deduplicates `events`, which must not end up empty.
+fn normalize_events(events: Vec<WebhookEvent>) -> Result<serde_json::Value> {
+    if events.is_empty() {
+        return Err(Error::unprocessable_entity([(
+            "events",
+            "at least one event is required",
+        )]));
+    }
+
+    let events: Vec<WebhookEvent> = events
+        .into_iter()
+        .sorted_by_key(|event| event.as_str())
+        .dedup()
+        .collect();
+
+    Ok(serde_json::to_value(events).expect("events serialize to JSON"))
+}
+
+fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::random();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+async fn list_webhooks(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+) -> Result<Json<MultipleWebhooksBody>> {
+    let webhooks = sqlx::query_as!(
+        WebhookFromQuery,
+        r#"
+select
+    webhook_id `webhook_id: DbUuid`,
+    user_id `user_id: DbUuid`,
+    url,
+    events,
+    global `global: bool`,
+    active `active: bool`,
+    created_at `created_at: Timestamptz`,
+    updated_at `updated_at: Timestamptz`
+from webhook
+where user_id = ?
+order by webhook_id
+        "#,
+        DbUuid(auth_user.user_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?
+        .into_iter()
+        .map(WebhookFromQuery::into_webhook)
+        .collect();
+
+    Ok(Json(MultipleWebhooksBody { webhooks }))
+}
+
+async fn create_webhook(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Json(req): Json<WebhookBody<CreateWebhook>>,
+) -> Result<Json<WebhookBody>> {
+    validate_url(&ctx, &req.webhook.url).await?;
+    let events = normalize_events(req.webhook.events)?;
+
+    if req.webhook.global && !ctx.config.admin_user_ids.contains(&auth_user.user_id) {
+        return Err(Error::Forbidden);
+    }
+
+    let webhook_id = DbUuid::new_ordered();
+    let secret = generate_secret();
+
+    sqlx::query!(
+        r#"
+insert into webhook (webhook_id, user_id, url, secret, events, global) values (?, ?, ?, ?, ?, ?)
+        "#,
+        webhook_id,
+        DbUuid(auth_user.user_id),
+        req.webhook.url,
+        secret,
+        events,
+        req.webhook.global
+    )
+        .execute(&ctx.db)
+        .await?;
+
+    let mut webhook = own_webhook(&ctx, webhook_id.0, auth_user.user_id).await?;
+    webhook.secret = Some(secret);
+
+    Ok(Json(WebhookBody { webhook }))
+}
+
+async fn get_webhook(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<Json<WebhookBody>> {
+    let webhook = own_webhook(&ctx, webhook_id, auth_user.user_id).await?;
+
+    Ok(Json(WebhookBody { webhook }))
+}
+
+/// Deactivating a webhook holds its pending deliveries until it is activated again.
+async fn update_webhook(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Path(webhook_id): Path<Uuid>,
+    Json(req): Json<WebhookBody<UpdateWebhook>>,
+) -> Result<Json<WebhookBody>> {
+    if let Some(url) = &req.webhook.url {
+        validate_url(&ctx, url).await?;
+    }
+
+    let events = req.webhook.events.map(normalize_events).transpose()?;
+
+    own_webhook(&ctx, webhook_id, auth_user.user_id).await?;
+
+    sqlx::query!(
+        r#"
+update webhook
+set
+    url = coalesce(?, url),
+    events = coalesce(?, events),
+    active = coalesce(?, active)
+where webhook_id = ?
+        "#,
+        req.webhook.url,
+        events,
+        req.webhook.active,
+        DbUuid(webhook_id)
+    )
+        .execute(&ctx.db)
+        .await?;
+
+    let webhook = own_webhook(&ctx, webhook_id, auth_user.user_id).await?;
+
+    Ok(Json(WebhookBody { webhook }))
+}
+
+async fn delete_webhook(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<()> {
+    own_webhook(&ctx, webhook_id, auth_user.user_id).await?;
+
+    sqlx::query!(
+        r#"
+delete from webhook where webhook_id = ?
+        "#,
+        DbUuid(webhook_id)
+    )
+        .execute(&ctx.db)
+        .await?;
+
+    Ok(())
+}
+
+/// The delivery log of a webhook, newest first.
+async fn list_deliveries(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Path(webhook_id): Path<Uuid>,
+    Query(query): Query<ListDeliveriesQuery>,
+) -> Result<Json<MultipleDeliveriesBody>> {
+    own_webhook(&ctx, webhook_id, auth_user.user_id).await?;
+
+    let deliveries = sqlx::query_as!(
+        DeliveryFromQuery,
+        r#"
+select
+    delivery.delivery_id,
+    delivery.event_id,
+    webhook_event.event `event: WebhookEvent`,
+    delivery.status `status: DeliveryStatus`,
+    delivery.next_attempt_at `next_attempt_at: Timestamptz`,
+    delivery.delivered_at `delivered_at: Timestamptz`,
+    delivery.created_at `created_at: Timestamptz`
+from webhook_delivery delivery
+inner join webhook_event on webhook_event.event_id = delivery.event_id
+where delivery.webhook_id = ?
+order by delivery.delivery_id desc
+limit ? offset ?
+        "#,
+        DbUuid(webhook_id),
+        query
+            .limit
+            .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+            .clamp(1, MAX_DELIVERY_LIMIT),
+        query.offset.unwrap_or(0).max(0)
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    // Deliveries are listed by ID, so the page covers every delivery of the webhook between
+    // its first and last ID.
+    let mut attempts = match (deliveries.last(), deliveries.first()) {
+        (Some(first), Some(last)) => {
+            attempts_by_delivery(&ctx, webhook_id, first.delivery_id, last.delivery_id).await?
+        }
+        _ => HashMap::new(),
+    };
+
+    let deliveries = deliveries
+        .into_iter()
+        .map(|delivery| {
+            let attempts = attempts.remove(&delivery.delivery_id).unwrap_or_default();
+            delivery.into_delivery(attempts)
+        })
+        .collect();
+
+    Ok(Json(MultipleDeliveriesBody { deliveries }))
+}
+
+/// Sends a delivery again as soon as the worker gets to it, whatever its status, with a
+/// fresh set of attempts. The event keeps its ID, so receivers can tell it is a replay.
+async fn replay_delivery(
+    auth_user: AuthUser,
+    ctx: Extension<ApiContext>,
+    Path((webhook_id, delivery_id)): Path<(Uuid, i64)>,
+) -> Result<Json<DeliveryBody>> {
+    own_webhook(&ctx, webhook_id, auth_user.user_id).await?;
+
+    let result = sqlx::query!(
+        r#"
+update webhook_delivery
+set status = 'pending', attempts = 0, next_attempt_at = current_timestamp, delivered_at = null
+where delivery_id = ? and webhook_id = ?
+        "#,
+        delivery_id,
+        DbUuid(webhook_id)
+    )
+        .execute(&ctx.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+
+    let delivery = sqlx::query_as!(
+        DeliveryFromQuery,
+        r#"
+select
+    delivery.delivery_id,
+    delivery.event_id,
+    webhook_event.event `event: WebhookEvent`,
+    delivery.status `status: DeliveryStatus`,
+    delivery.next_attempt_at `next_attempt_at: Timestamptz`,
+    delivery.delivered_at `delivered_at: Timestamptz`,
+    delivery.created_at `created_at: Timestamptz`
+from webhook_delivery delivery
+inner join webhook_event on webhook_event.event_id = delivery.event_id
+where delivery.delivery_id = ?
+        "#,
+        delivery_id
+    )
+        .fetch_one(&ctx.db)
+        .await?;
+
+    let mut attempts = attempts_by_delivery(&ctx, webhook_id, delivery_id, delivery_id).await?;
+
+    Ok(Json(DeliveryBody {
+        delivery: delivery.into_delivery(attempts.remove(&delivery_id).unwrap_or_default()),
+    }))
+}
+
+/// The webhook `webhook_id`, provided `user_id` owns it.
+async fn own_webhook(ctx: &ApiContext, webhook_id: Uuid, user_id: Uuid) -> Result<Webhook> {
+    let webhook = sqlx::query_as!(
+        WebhookFromQuery,
+        r#"
+select
+    webhook_id `webhook_id: DbUuid`,
+    user_id `user_id: DbUuid`,
+    url,
+    events,
+    global `global: bool`,
+    active `active: bool`,
+    created_at `created_at: Timestamptz`,
+    updated_at `updated_at: Timestamptz`
+from webhook
+where webhook_id = ?
+        "#,
+        DbUuid(webhook_id)
+    )
+        .fetch_optional(&ctx.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    if webhook.user_id.0 != user_id {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(webhook.into_webhook())
+}
+
+/// The attempts of the deliveries of `webhook_id` with IDs from `first` to `last`, oldest
+/// first.
+async fn attempts_by_delivery(
+    ctx: &ApiContext,
+    webhook_id: Uuid,
+    first: i64,
+    last: i64,
+) -> Result<HashMap<i64, Vec<Attempt>>> {
+    let attempts = sqlx::query_as!(
+        AttemptFromQuery,
+        r#"
+select
+    attempt.delivery_id,
+    attempt.status_code,
+    attempt.error,
+    attempt.duration_ms,
+    attempt.created_at `created_at: Timestamptz`
+from webhook_attempt attempt
+inner join webhook_delivery delivery on delivery.delivery_id = attempt.delivery_id
+where attempt.delivery_id between ? and ?
+and delivery.webhook_id = ?
+order by attempt.attempt_id
+        "#,
+        first,
+        last,
+        DbUuid(webhook_id)
+    )
+        .fetch_all(&ctx.db)
+        .await?;
+
+    Ok(attempts
+        .into_iter()
+        .map(|attempt| {
+            (
+                attempt.delivery_id,
+                Attempt {
+                    status_code: attempt.status_code,
+                    error: attempt.error,
+                    duration_ms: attempt.duration_ms,
+                    created_at: attempt.created_at,
+                },
+            )
+        })
+        .into_group_map())
+}
@@ -0,0 +1,108 @@
+//! Keeping webhooks away from the server's own network.
+//!
+//! Webhook URLs are checked when they are saved, but a host name can resolve to a different
+//! address by the time a delivery is sent. The worker's client therefore resolves names
+//! itself and drops every address that isn't public.
+
+use std::error::Error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::Url;
+
+/// Resolves host names to their public addresses only.
+pub(super) struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            // The port is replaced by the URL's.
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+
+            Ok::<_, Box<dyn Error + Send + Sync>>(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether every address the host of `url` resolves to is public.
+pub(super) async fn resolves_to_public(url: &Url) -> bool {
+    if let Some(ip) = host_ip(url) {
+        return is_public_ip(ip);
+    }
+
+    let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => (host, port),
+        _ => return false,
+    };
+
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => {
+            let mut addrs = addrs.peekable();
+            addrs.peek().is_some() && addrs.all(|addr| is_public_ip(addr.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+/// The host of `url` if it is an IP address, which is connected to without resolving it.
+pub(super) fn host_ip(url: &Url) -> Option<IpAddr> {
+    // IPv6 hosts are written in brackets.
+    url.host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether `ip` is reachable on the internet, as opposed to loopback, private, link-local
+/// (which includes cloud metadata services at `169.254.169.254`) and other special addresses.
+pub(super) fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network", 0.0.0.0/8.
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10.
+        || (a == 100 && b & 0xc0 == 64)
+        // Reserved, 240.0.0.0/4.
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7.
+        || first & 0xfe00 == 0xfc00
+        // Link-local, fe80::/10, and the deprecated site-local fec0::/10.
+        || first & 0xffc0 == 0xfe80
+        || first & 0xffc0 == 0xfec0
+        // Documentation, 2001:db8::/32.
+        || (first == 0x2001 && ip.segments()[1] == 0xdb8))
+}
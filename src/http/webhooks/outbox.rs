@@ -0,0 +1,67 @@
+use anyhow::Context;
+use sqlx::{MySql, Transaction};
+use uuid::Uuid;
+
+use crate::http::{types::DbUuid, Result};
+
+#[derive(sqlx::Type, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WebhookEvent {
+    /// The article became publicly visible: it was created as published, or published by its
+    /// author or the scheduler. Drafts, scheduled articles included, don't send it before then,
+    /// and an article sends it again when published after being unpublished or archived.
+    #[sqlx(rename = "article.created")]
+    #[serde(rename = "article.created")]
+    ArticleCreated,
+    /// The article was edited, unpublished, archived or restored.
+    #[sqlx(rename = "article.updated")]
+    #[serde(rename = "article.updated")]
+    ArticleUpdated,
+    #[sqlx(rename = "article.deleted")]
+    #[serde(rename = "article.deleted")]
+    ArticleDeleted,
+    #[sqlx(rename = "comment.created")]
+    #[serde(rename = "comment.created")]
+    CommentCreated,
+    #[sqlx(rename = "user.followed")]
+    #[serde(rename = "user.followed")]
+    UserFollowed,
+}
+
+impl WebhookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ArticleCreated => "article.created",
+            Self::ArticleUpdated => "article.updated",
+            Self::ArticleDeleted => "article.deleted",
+            Self::CommentCreated => "comment.created",
+            Self::UserFollowed => "user.followed",
+        }
+    }
+}
+
+/// Records an event for the webhooks of `user_id` and the global ones.
+///
+/// Called within the transaction making the change, so the event is delivered if and only
+/// if the change is committed. The webhook worker picks it up from there.
+pub(in crate::http) async fn enqueue(
+    tx: &mut Transaction<'_, MySql>,
+    event: WebhookEvent,
+    user_id: Uuid,
+    data: &impl serde::Serialize,
+) -> Result<()> {
+    let data = serde_json::to_value(data)
+        .with_context(|| format!("failed to serialize {:?} webhook event", event))?;
+
+    sqlx::query!(
+        r#"
+insert into webhook_event (event, user_id, data) values (?, ?, ?)
+        "#,
+        event,
+        DbUuid(user_id),
+        data
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
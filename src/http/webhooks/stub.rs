@@ -0,0 +1,135 @@
+//! A webhook receiver listening on the loopback interface, for tests to point webhooks at.
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use axum::{
+    body::Bytes,
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    AddExtensionLayer, Router,
+};
+use tokio::task::JoinHandle;
+
+use super::worker::sign;
+
+pub struct WebhookStubReceiver {
+    addr: SocketAddr,
+    state: Arc<Mutex<StubState>>,
+    server: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct StubState {
+    received: Vec<ReceivedWebhook>,
+    /// Requests still to be answered with a 500, to exercise retries.
+    failures_left: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReceivedWebhook {
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    /// Whether the receiver answered with an error.
+    pub failed: bool,
+}
+
+impl WebhookStubReceiver {
+    /// Starts the receiver on a free port of `127.0.0.1`.
+    pub fn start() -> anyhow::Result<Self> {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").context("failed to bind the stub receiver")?;
+        let addr = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(StubState::default()));
+
+        let app = Router::new()
+            .route("/webhook", post(receive))
+            .layer(AddExtensionLayer::new(state.clone()));
+
+        let server = axum::Server::from_tcp(listener)?.serve(app.into_make_service());
+
+        let server = tokio::spawn(async move {
+            if let Err(e) = server.await {
+                log::error!("webhook stub receiver failed: {}", e);
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            server,
+        })
+    }
+
+    /// The URL to register as a webhook.
+    pub fn url(&self) -> String {
+        format!("http://{}/webhook", self.addr)
+    }
+
+    /// Answers the next `count` requests with `500 Internal Server Error`.
+    pub fn fail_next(&self, count: usize) {
+        self.state.lock().expect("stub lock poisoned").failures_left = count;
+    }
+
+    /// Every request received so far, failed ones included.
+    pub fn received(&self) -> Vec<ReceivedWebhook> {
+        self.state.lock().expect("stub lock poisoned").received.clone()
+    }
+}
+
+impl Drop for WebhookStubReceiver {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl ReceivedWebhook {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    pub fn json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_slice(&self.body)
+    }
+
+    /// Checks `X-Webhook-Signature` the way a receiver would.
+    pub fn has_valid_signature(&self, secret: &str) -> bool {
+        let timestamp = match self
+            .header("x-webhook-timestamp")
+            .and_then(|timestamp| timestamp.parse().ok())
+        {
+            Some(timestamp) => timestamp,
+            None => return false,
+        };
+
+        self.header("x-webhook-signature") == Some(sign(secret, timestamp, &self.body).as_str())
+    }
+}
+
+async fn receive(
+    Extension(state): Extension<Arc<Mutex<StubState>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let mut state = state.lock().expect("stub lock poisoned");
+
+    let failed = state.failures_left > 0;
+    if failed {
+        state.failures_left -= 1;
+    }
+
+    state.received.push(ReceivedWebhook {
+        headers,
+        body,
+        failed,
+    });
+
+    if failed {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
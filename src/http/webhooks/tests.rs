@@ -0,0 +1,349 @@
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+use tokio::time::{self, Instant};
+
+use crate::http::test_support::{TestApp, TestUser};
+
+use super::network::is_public_ip;
+use super::stub::WebhookStubReceiver;
+use super::worker::{process_webhooks, retry_delay_seconds, sign};
+
+const MAX_ATTEMPTS: i32 = 3;
+
+/// How long to keep running the worker for a delivery log to reach the expected state.
+const PROCESS_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct TestWebhook {
+    id: String,
+    secret: String,
+}
+
+async fn app() -> TestApp {
+    TestApp::new(&["--webhook-allow-private-networks"]).await.unwrap()
+}
+
+async fn create_webhook(app: &TestApp, owner: &TestUser, url: &str) -> TestWebhook {
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/api/webhooks",
+            Some(&owner.token),
+            Some(json!({ "webhook": { "url": url, "events": ["article.created"] } })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    TestWebhook {
+        id: body["webhook"]["id"].as_str().unwrap().to_string(),
+        secret: body["webhook"]["secret"].as_str().unwrap().to_string(),
+    }
+}
+
+async fn delivery_log(app: &TestApp, owner: &TestUser, webhook: &TestWebhook) -> Vec<Value> {
+    let (status, body) = app
+        .request(
+            Method::GET,
+            &format!("/api/webhooks/{}/deliveries", webhook.id),
+            Some(&owner.token),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    serde_json::from_value(body["deliveries"].clone()).unwrap()
+}
+
+/// Runs the worker until the delivery log of `webhook` satisfies `done`, returning the log.
+///
+/// Other tests run the worker at the same time and may send the deliveries of this one, so
+/// the number of deliveries a single run attempted says nothing.
+async fn process_until(
+    app: &TestApp,
+    owner: &TestUser,
+    webhook: &TestWebhook,
+    done: impl Fn(&[Value]) -> bool,
+) -> Vec<Value> {
+    let deadline = Instant::now() + PROCESS_TIMEOUT;
+
+    loop {
+        process_webhooks(&app.db, MAX_ATTEMPTS, true).await.unwrap();
+
+        let log = delivery_log(app, owner, webhook).await;
+
+        if done(&log) {
+            return log;
+        }
+
+        assert!(Instant::now() < deadline, "unexpected delivery log: {:?}", log);
+        time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn delivered(log: &[Value], attempts: usize) -> bool {
+    log.len() == 1
+        && log[0]["status"] == "delivered"
+        && log[0]["attempts"].as_array().map_or(0, Vec::len) == attempts
+}
+
+#[test]
+fn signature_is_hmac_sha256_of_timestamp_and_body() {
+    assert_eq!(
+        sign("whsec", 1_700_000_000, br#"{"eventId":1}"#),
+        "sha256=16d0890a909ea6b2d3e391753fedbcfda17936855810a22b9b31992d9f7ba6d1"
+    );
+}
+
+#[test]
+fn retry_delay_doubles_up_to_the_maximum() {
+    assert_eq!(retry_delay_seconds(1), 30);
+    assert_eq!(retry_delay_seconds(2), 60);
+    assert_eq!(retry_delay_seconds(3), 120);
+    assert_eq!(retry_delay_seconds(100), 6 * 60 * 60);
+}
+
+#[test]
+fn only_public_addresses_are_public() {
+    for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+        assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+    }
+
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "255.255.255.255",
+        "::1",
+        "::",
+        "fe80::1",
+        "fd00::1",
+        "::ffff:127.0.0.1",
+        "::ffff:169.254.169.254",
+    ] {
+        assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+    }
+}
+
+#[tokio::test]
+async fn webhooks_to_private_addresses_are_refused() {
+    let app = TestApp::new(&[]).await.unwrap();
+    let owner = app.register_user().await.unwrap();
+
+    for url in [
+        "http://127.0.0.1:8080/hook",
+        "http://localhost/hook",
+        "http://10.0.0.1/hook",
+        "http://169.254.169.254/latest/meta-data/",
+        "http://[::1]/hook",
+    ] {
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/api/webhooks",
+                Some(&owner.token),
+                Some(json!({ "webhook": { "url": url, "events": ["article.created"] } })),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}: {}", url, body);
+    }
+}
+
+#[tokio::test]
+async fn deliveries_are_signed() {
+    let app = app().await;
+    let owner = app.register_user().await.unwrap();
+    let stub = WebhookStubReceiver::start().unwrap();
+    let webhook = create_webhook(&app, &owner, &stub.url()).await;
+
+    let slug = app.create_article(&owner).await.unwrap();
+
+    process_until(&app, &owner, &webhook, |log| delivered(log, 1)).await;
+
+    let received = stub.received();
+    assert_eq!(received.len(), 1);
+
+    let request = &received[0];
+    assert!(request.has_valid_signature(&webhook.secret));
+    assert!(!request.has_valid_signature("not the secret"));
+    assert_eq!(request.header("x-webhook-event"), Some("article.created"));
+
+    let payload = request.json().unwrap();
+    assert_eq!(payload["event"], "article.created");
+    assert_eq!(payload["data"]["article"]["slug"], slug.as_str());
+}
+
+#[tokio::test]
+async fn drafts_are_announced_when_published() {
+    let app = app().await;
+    let owner = app.register_user().await.unwrap();
+    let stub = WebhookStubReceiver::start().unwrap();
+    let webhook = create_webhook(&app, &owner, &stub.url()).await;
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/api/articles",
+            Some(&owner.token),
+            Some(json!({
+                "article": {
+                    "title": format!("Draft of {}", owner.username),
+                    "description": "A test draft",
+                    "body": "Not yet.",
+                    "tag_list": [],
+                    "status": "draft",
+                }
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let slug = body["article"]["slug"].as_str().unwrap().to_string();
+
+    process_webhooks(&app.db, MAX_ATTEMPTS, true).await.unwrap();
+    assert_eq!(delivery_log(&app, &owner, &webhook).await, Vec::<Value>::new());
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/articles/{}/publish", slug),
+            Some(&owner.token),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    process_until(&app, &owner, &webhook, |log| delivered(log, 1)).await;
+
+    let payload = stub.received()[0].json().unwrap();
+    assert_eq!(payload["event"], "article.created");
+    assert_eq!(payload["data"]["article"]["slug"], slug.as_str());
+    assert_eq!(payload["data"]["article"]["status"], "published");
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let app = app().await;
+    let owner = app.register_user().await.unwrap();
+    let stub = WebhookStubReceiver::start().unwrap();
+    let webhook = create_webhook(&app, &owner, &stub.url()).await;
+
+    stub.fail_next(1);
+    app.create_article(&owner).await.unwrap();
+
+    let log = process_until(&app, &owner, &webhook, |log| {
+        log.len() == 1 && log[0]["attempts"].as_array().map_or(0, Vec::len) == 1
+    })
+        .await;
+
+    let delivery = &log[0];
+    assert_eq!(delivery["status"], "pending");
+    assert_eq!(delivery["attempts"][0]["statusCode"], 500);
+    assert!(delivery["attempts"][0]["error"].is_string());
+
+    let delivery_id = delivery["id"].as_i64().unwrap();
+
+    let delay: i64 = sqlx::query_scalar(
+        "select timestampdiff(second, current_timestamp, next_attempt_at) \
+         from webhook_delivery where delivery_id = ?",
+    )
+        .bind(delivery_id)
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert!(
+        (retry_delay_seconds(1) - 5..=retry_delay_seconds(1)).contains(&delay),
+        "retried in {} seconds",
+        delay
+    );
+
+    // Skip the wait.
+    sqlx::query(
+        "update webhook_delivery set next_attempt_at = current_timestamp where delivery_id = ?",
+    )
+        .bind(delivery_id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let log = process_until(&app, &owner, &webhook, |log| delivered(log, 2)).await;
+    assert_eq!(log[0]["attempts"][1]["statusCode"], 204);
+    assert!(log[0]["attempts"][1]["error"].is_null());
+    assert!(log[0]["nextAttemptAt"].is_null());
+
+    let received = stub.received();
+    assert_eq!(
+        received.iter().map(|request| request.failed).collect::<Vec<_>>(),
+        [true, false]
+    );
+    assert_eq!(
+        received[0].header("x-webhook-delivery"),
+        received[1].header("x-webhook-delivery")
+    );
+}
+
+#[tokio::test]
+async fn delivery_log_is_private() {
+    let app = app().await;
+    let owner = app.register_user().await.unwrap();
+    let other = app.register_user().await.unwrap();
+    let stub = WebhookStubReceiver::start().unwrap();
+    let webhook = create_webhook(&app, &owner, &stub.url()).await;
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &format!("/api/webhooks/{}/deliveries", webhook.id),
+            Some(&other.token),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn deliveries_can_be_replayed() {
+    let app = app().await;
+    let owner = app.register_user().await.unwrap();
+    let stub = WebhookStubReceiver::start().unwrap();
+    let webhook = create_webhook(&app, &owner, &stub.url()).await;
+
+    app.create_article(&owner).await.unwrap();
+
+    let log = process_until(&app, &owner, &webhook, |log| delivered(log, 1)).await;
+    let delivery_id = log[0]["id"].as_i64().unwrap();
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/api/webhooks/{}/deliveries/{}/replay", webhook.id, delivery_id),
+            Some(&owner.token),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["delivery"]["status"], "pending");
+    assert!(body["delivery"]["deliveredAt"].is_null());
+
+    process_until(&app, &owner, &webhook, |log| delivered(log, 2)).await;
+
+    let received = stub.received();
+    assert_eq!(received.len(), 2);
+
+    let (first, replayed) = (received[0].json().unwrap(), received[1].json().unwrap());
+    assert_eq!(first["eventId"], replayed["eventId"]);
+    assert!(replayed["eventId"].is_i64());
+    assert!(received[1].has_valid_signature(&webhook.secret));
+}
@@ -0,0 +1,440 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use sqlx::MySqlPool;
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+
+use crate::http::{types::Timestamptz, Result};
+
+use super::network::{host_ip, is_public_ip, PublicResolver};
+use super::WebhookEvent;
+
+const WORKER_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum number of events fanned out into deliveries per transaction.
+const DISPATCH_BATCH_SIZE: i64 = 100;
+
+/// Maximum number of deliveries sent concurrently.
+const DELIVERY_BATCH_SIZE: i64 = 20;
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a claimed delivery is left to the worker sending it. If that worker dies, another
+/// one retries the delivery after this.
+const DELIVERY_LEASE_SECONDS: i64 = 60;
+
+/// Delay before the first retry, doubled with every further attempt.
+const RETRY_BASE_SECONDS: i64 = 30;
+
+const MAX_RETRY_DELAY_SECONDS: i64 = 6 * 60 * 60;
+
+/// Events are kept this long after they were fanned out, along with their delivery log.
+const EVENT_RETENTION_DAYS: i64 = 30;
+
+/// Longest error message kept in `webhook_attempt`.
+const MAX_ERROR_LENGTH: usize = 1024;
+
+/// The body POSTed to a webhook.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Payload<'a> {
+    event_id: i64,
+    event: WebhookEvent,
+    created_at: Timestamptz,
+    data: &'a serde_json::Value,
+}
+
+struct DueDelivery {
+    delivery_id: i64,
+    attempts: i32,
+    event_id: i64,
+    event: WebhookEvent,
+    data: serde_json::Value,
+    created_at: Timestamptz,
+    url: String,
+    secret: String,
+}
+
+/// The HTTP client deliveries are sent with.
+struct WebhookClient {
+    client: reqwest::Client,
+    /// Skips the checks keeping deliveries away from non-public addresses.
+    allow_private_networks: bool,
+}
+
+struct Outcome {
+    status_code: Option<u16>,
+    error: Option<String>,
+    duration_ms: i64,
+}
+
+impl Outcome {
+    fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Periodically fans out new webhook events into deliveries and sends those that are due.
+///
+/// Events and deliveries are claimed with `for update skip locked`, so several server
+/// instances can run the worker against the same database. A delivery is retried with
+/// exponential backoff until it succeeds or `max_attempts` is reached.
+pub(in crate::http) fn spawn_webhook_worker(
+    db: MySqlPool,
+    max_attempts: i32,
+    allow_private_networks: bool,
+) -> JoinHandle<()> {
+    let client = WebhookClient::new(allow_private_networks);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WORKER_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = process(&db, &client, max_attempts).await {
+                log::error!("failed to process webhooks: {:?}", e);
+            }
+
+            match purge_old_events(&db).await {
+                Ok(purged) if purged > 0 => log::info!("purged {} old webhook events", purged),
+                Ok(_) => (),
+                Err(e) => log::error!("failed to purge old webhook events: {:?}", e),
+            }
+        }
+    })
+}
+
+/// Does what the worker does on every tick, right away: fans out pending events and sends
+/// the deliveries that are due. Returns the number of deliveries attempted.
+///
+/// Tests use this instead of waiting for the worker.
+#[cfg(test)]
+pub(super) async fn process_webhooks(
+    db: &MySqlPool,
+    max_attempts: i32,
+    allow_private_networks: bool,
+) -> Result<usize> {
+    process(db, &WebhookClient::new(allow_private_networks), max_attempts).await
+}
+
+impl WebhookClient {
+    fn new(allow_private_networks: bool) -> Self {
+        let mut builder = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent(concat!("conduit-webhooks/", env!("CARGO_PKG_VERSION")));
+
+        if !allow_private_networks {
+            // Through a proxy, the proxy would resolve the host instead of `PublicResolver`.
+            builder = builder.dns_resolver(Arc::new(PublicResolver)).no_proxy();
+        }
+
+        Self {
+            client: builder.build().expect("the HTTP client configuration is valid"),
+            allow_private_networks,
+        }
+    }
+}
+
+async fn process(db: &MySqlPool, client: &WebhookClient, max_attempts: i32) -> Result<usize> {
+    while dispatch_events(db).await? == DISPATCH_BATCH_SIZE as usize {}
+
+    let mut attempted = 0;
+
+    loop {
+        let sent = send_due_deliveries(db, client, max_attempts).await?;
+        attempted += sent;
+
+        if sent < DELIVERY_BATCH_SIZE as usize {
+            return Ok(attempted);
+        }
+    }
+}
+
+/// Creates a delivery of each new event for every active webhook subscribed to it.
+async fn dispatch_events(db: &MySqlPool) -> Result<usize> {
+    let mut tx = db.begin().await?;
+
+    let event_ids = sqlx::query_scalar!(
+        r#"
+select event_id from webhook_event
+where dispatched_at is null
+order by event_id
+limit ?
+for update skip locked
+        "#,
+        DISPATCH_BATCH_SIZE
+    )
+        .fetch_all(&mut tx)
+        .await?;
+
+    if event_ids.is_empty() {
+        return Ok(0);
+    }
+
+    // One statement per event: matching a list of IDs in a single statement makes MySQL scan
+    // and lock far more of the table than the rows claimed above.
+    for &event_id in &event_ids {
+        sqlx::query!(
+            r#"
+insert into webhook_delivery (webhook_id, event_id)
+select webhook.webhook_id, webhook_event.event_id
+from webhook_event
+inner join webhook
+    on webhook.active
+    and JSON_CONTAINS(webhook.events, JSON_QUOTE(webhook_event.event))
+    and (webhook.global or webhook.user_id = webhook_event.user_id)
+where webhook_event.event_id = ?
+            "#,
+            event_id
+        )
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query!(
+            r#"
+update webhook_event set dispatched_at = current_timestamp
+where event_id = ?
+            "#,
+            event_id
+        )
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(event_ids.len())
+}
+
+/// Claims a batch of due deliveries, sends them and records the outcome.
+///
+/// The claim only pushes `next_attempt_at` back by `DELIVERY_LEASE_SECONDS`, so no lock is
+/// held while waiting on receivers.
+async fn send_due_deliveries(
+    db: &MySqlPool,
+    client: &WebhookClient,
+    max_attempts: i32,
+) -> Result<usize> {
+    let mut tx = db.begin().await?;
+
+    let deliveries = sqlx::query_as!(
+        DueDelivery,
+        r#"
+select
+    delivery.delivery_id,
+    delivery.attempts,
+    webhook_event.event_id,
+    webhook_event.event `event: WebhookEvent`,
+    webhook_event.data,
+    webhook_event.created_at `created_at: Timestamptz`,
+    webhook.url,
+    webhook.secret
+from webhook_delivery delivery
+inner join webhook on webhook.webhook_id = delivery.webhook_id
+inner join webhook_event on webhook_event.event_id = delivery.event_id
+where delivery.status = 'pending'
+and delivery.next_attempt_at <= current_timestamp
+and webhook.active
+order by delivery.next_attempt_at
+limit ?
+for update of delivery skip locked
+        "#,
+        DELIVERY_BATCH_SIZE
+    )
+        .fetch_all(&mut tx)
+        .await?;
+
+    if deliveries.is_empty() {
+        return Ok(0);
+    }
+
+    for delivery in &deliveries {
+        sqlx::query!(
+            r#"
+update webhook_delivery
+set next_attempt_at = current_timestamp + interval ? second
+where delivery_id = ?
+            "#,
+            DELIVERY_LEASE_SECONDS,
+            delivery.delivery_id
+        )
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    let outcomes =
+        futures::future::join_all(deliveries.iter().map(|delivery| send(client, delivery))).await;
+
+    let mut tx = db.begin().await?;
+
+    for (delivery, outcome) in deliveries.iter().zip(&outcomes) {
+        sqlx::query!(
+            r#"
+insert into webhook_attempt (delivery_id, status_code, error, duration_ms) values (?, ?, ?, ?)
+            "#,
+            delivery.delivery_id,
+            outcome.status_code,
+            outcome.error,
+            outcome.duration_ms
+        )
+            .execute(&mut tx)
+            .await?;
+
+        let attempts = delivery.attempts + 1;
+
+        if outcome.succeeded() {
+            sqlx::query!(
+                r#"
+update webhook_delivery
+set status = 'delivered', attempts = ?, delivered_at = current_timestamp
+where delivery_id = ?
+                "#,
+                attempts,
+                delivery.delivery_id
+            )
+                .execute(&mut tx)
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+update webhook_delivery
+set
+    status = if(? >= ?, 'failed', 'pending'),
+    attempts = ?,
+    next_attempt_at = current_timestamp + interval ? second
+where delivery_id = ?
+                "#,
+                attempts,
+                max_attempts,
+                attempts,
+                retry_delay_seconds(attempts),
+                delivery.delivery_id
+            )
+                .execute(&mut tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(deliveries.len())
+}
+
+/// Seconds to wait after the `attempts`-th failed attempt.
+pub(super) fn retry_delay_seconds(attempts: i32) -> i64 {
+    let doublings = (attempts - 1).clamp(0, 20) as u32;
+
+    (RETRY_BASE_SECONDS << doublings).min(MAX_RETRY_DELAY_SECONDS)
+}
+
+async fn send(client: &WebhookClient, delivery: &DueDelivery) -> Outcome {
+    // Addresses in URLs are connected to without going through the resolver.
+    let ip = reqwest::Url::parse(&delivery.url)
+        .ok()
+        .and_then(|url| host_ip(&url));
+
+    if !client.allow_private_networks && matches!(ip, Some(ip) if !is_public_ip(ip)) {
+        return Outcome {
+            status_code: None,
+            error: Some("refusing to connect to a non-public address".into()),
+            duration_ms: 0,
+        };
+    }
+
+    let body = serde_json::to_vec(&Payload {
+        event_id: delivery.event_id,
+        event: delivery.event,
+        created_at: delivery.created_at,
+        data: &delivery.data,
+    })
+    .expect("payloads serialize to JSON");
+
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+    let started = Instant::now();
+
+    let result = client
+        .client
+        .post(&delivery.url)
+        .header("content-type", "application/json")
+        .header("x-webhook-event", delivery.event.as_str())
+        .header("x-webhook-delivery", delivery.delivery_id)
+        .header("x-webhook-timestamp", timestamp)
+        .header("x-webhook-signature", sign(&delivery.secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await;
+
+    let duration_ms = started.elapsed().as_millis() as i64;
+
+    match result {
+        Ok(response) if response.status().is_success() => Outcome {
+            status_code: Some(response.status().as_u16()),
+            error: None,
+            duration_ms,
+        },
+        Ok(response) => Outcome {
+            status_code: Some(response.status().as_u16()),
+            error: Some(format!("receiver responded with {}", response.status())),
+            duration_ms,
+        },
+        Err(e) => {
+            let mut error = e.to_string();
+            truncate(&mut error, MAX_ERROR_LENGTH);
+
+            Outcome {
+                status_code: None,
+                error: Some(error),
+                duration_ms,
+            }
+        }
+    }
+}
+
+/// The `X-Webhook-Signature` of a payload: `sha256=` followed by the hex-encoded
+/// HMAC-SHA256 of `{timestamp}.{body}`, keyed with the webhook's secret.
+///
+/// Receivers should compute it themselves, compare in constant time and reject old
+/// timestamps to guard against replayed requests.
+pub(super) fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC-SHA-256 can accept any key length");
+
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+fn truncate(string: &mut String, max_len: usize) {
+    if string.len() > max_len {
+        let mut end = max_len;
+        while !string.is_char_boundary(end) {
+            end -= 1;
+        }
+        string.truncate(end);
+    }
+}
+
+/// Deletes events fanned out more than `EVENT_RETENTION_DAYS` ago, with their deliveries.
+async fn purge_old_events(db: &MySqlPool) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"
+delete from webhook_event
+where dispatched_at < current_timestamp - interval ? day
+and event_id not in (select event_id from webhook_delivery where status = 'pending')
+        "#,
+        EVENT_RETENTION_DAYS
+    )
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -2,7 +2,7 @@ use anyhow::Context;
 use clap::Parser;
 use sqlx::mysql::MySqlPoolOptions;
 
-use axum_sqlx_mysql::config::Config;
+use axum_sqlx_mysql::config::{Command, Config};
 use axum_sqlx_mysql::http;
 
 #[tokio::main]
@@ -21,7 +21,13 @@ async fn main() -> anyhow::Result<()> {
 
     sqlx::migrate!().run(&db).await?;
 
-    http::serve(config, db).await?;
+    match config.command {
+        Some(Command::RecountArticles) => {
+            let updated = http::recount_article_counters(&db).await?;
+            log::info!("recounted counters, {} articles were out of date", updated);
+        }
+        Some(Command::Serve) | None => http::serve(config, db).await?,
+    }
 
     Ok(())
 }